@@ -0,0 +1,44 @@
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use std::str;
+use utf8_io::{Utf8Decoder, Utf8Validator};
+
+fuzz_target!(|bytes: &[u8]| {
+    // Decoding in pieces with a `Utf8Decoder` should produce the same output
+    // as `String::from_utf8_lossy`.
+    let lossy = String::from_utf8_lossy(bytes).to_string();
+    let mut decoder = Utf8Decoder::new();
+    let mut decoded = String::new();
+    let chunk_len = bytes.first().map_or(1, |b| usize::from(b % 7) + 1);
+    for mut chunk in bytes.chunks(chunk_len) {
+        decoder.decode(&mut chunk, &mut decoded, false).unwrap();
+        assert!(chunk.is_empty());
+    }
+    decoder.decode(&mut &b""[..], &mut decoded, true).unwrap();
+    assert_eq!(lossy, decoded);
+
+    // Validating in pieces with a `Utf8Validator` should agree with
+    // `str::from_utf8`.
+    let mut validator = Utf8Validator::new();
+    let mut validated = String::new();
+    let mut result = Ok(());
+    for mut chunk in bytes.chunks(3) {
+        result = validator.validate(&mut chunk, &mut validated, false);
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        result = validator.validate(&mut &b""[..], &mut validated, true);
+    }
+    match str::from_utf8(bytes) {
+        Ok(s) => assert_eq!(s, validated),
+        Err(error) => {
+            assert!(result.is_err());
+            assert_eq!(error.valid_up_to(), validated.len());
+        }
+    }
+});
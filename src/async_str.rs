@@ -0,0 +1,101 @@
+//! The parts of the async functions in the `tokio` and `futures_io` modules
+//! which don't depend on the runtime, written in terms of poll functions.
+
+use crate::read_str::poll_read_into_spare_capacity;
+use crate::DEFAULT_BUF_SIZE;
+use std::future::poll_fn;
+use std::io;
+use std::task::{Context, Poll};
+
+/// Implement `AsyncReadStr` and `AsyncWriteStr` for `&mut T` and `Box<T>`,
+/// forwarding to `T`, using whichever of those traits are in scope.
+macro_rules! impl_forwarding_async_str {
+    () => {
+        impl_forwarding_async_str!(&mut T);
+        impl_forwarding_async_str!(Box<T>);
+    };
+    ($ptr:ty) => {
+        impl<T: AsyncReadStr + Unpin + ?Sized> AsyncReadStr for $ptr {
+            #[inline]
+            fn poll_read_str(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut str,
+            ) -> Poll<io::Result<usize>> {
+                Pin::new(&mut **self).poll_read_str(cx, buf)
+            }
+        }
+
+        impl<T: AsyncWriteStr + Unpin + ?Sized> AsyncWriteStr for $ptr {
+            #[inline]
+            fn poll_write_str(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                s: &str,
+            ) -> Poll<io::Result<usize>> {
+                Pin::new(&mut **self).poll_write_str(cx, s)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_forwarding_async_str;
+
+/// Write all of `s` with `poll_write_str`.
+pub(crate) async fn write_str_with(
+    mut s: &str,
+    mut poll_write_str: impl FnMut(&mut Context<'_>, &str) -> Poll<io::Result<usize>>,
+) -> io::Result<()> {
+    while !s.is_empty() {
+        match poll_fn(|cx| poll_write_str(cx, s)).await {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => s = &s[n..],
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Copy everything from `poll_read_str` to `poll_write_str`, returning the
+/// number of bytes copied. This doesn't flush the output.
+pub(crate) async fn copy_str_with(
+    mut poll_read_str: impl FnMut(&mut Context<'_>, &mut str) -> Poll<io::Result<usize>>,
+    mut poll_write_str: impl FnMut(&mut Context<'_>, &str) -> Poll<io::Result<usize>>,
+) -> io::Result<u64> {
+    let mut buf = String::with_capacity(DEFAULT_BUF_SIZE);
+    let mut initialized = 0;
+
+    let mut written = 0;
+    loop {
+        // The bytes read last time are still initialized once cleared.
+        initialized += buf.len();
+        buf.clear();
+
+        let len = match poll_fn(|cx| {
+            // Safety: The spare capacity is zeroed before use, and only
+            // reused after `poll_read_str`, which leaves its buffer valid
+            // UTF-8.
+            unsafe {
+                poll_read_into_spare_capacity(&mut buf, DEFAULT_BUF_SIZE, &mut initialized, |s| {
+                    poll_read_str(cx, s)
+                })
+            }
+        })
+        .await
+        {
+            Ok(0) => break,
+            Ok(nread) => nread,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        write_str_with(&buf, &mut poll_write_str).await?;
+        written += len as u64;
+    }
+    Ok(written)
+}
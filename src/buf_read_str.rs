@@ -0,0 +1,87 @@
+use crate::ReadStr;
+use std::io::{self, BufRead};
+
+/// Extend the `BufRead` trait with `fill_buf_str`, a method for accessing
+/// buffered UTF-8 data, and methods built on it for reading lines.
+pub trait BufReadStr: BufRead + ReadStr {
+    /// Like [`BufRead::fill_buf`], but produces the result in a `str`. Use
+    /// [`BufRead::consume`] to mark bytes as consumed; the amount must be at
+    /// a `char` boundary.
+    fn fill_buf_str(&mut self) -> io::Result<&str>;
+
+    /// Like [`BufRead::read_line`], but avoids re-validating the data as
+    /// UTF-8.
+    #[inline]
+    fn read_line_str(&mut self, buf: &mut String) -> io::Result<usize> {
+        default_read_line_str(self, buf)
+    }
+
+    /// Like [`BufRead::lines`], but avoids re-validating the data as UTF-8.
+    #[inline]
+    fn lines_str(self) -> LinesStr<Self>
+    where
+        Self: Sized,
+    {
+        LinesStr { inner: self }
+    }
+}
+
+/// Default implementation of [`BufReadStr::read_line_str`].
+pub fn default_read_line_str<Inner: BufReadStr + ?Sized>(
+    inner: &mut Inner,
+    buf: &mut String,
+) -> io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let (done, used) = {
+            let available = match inner.fill_buf_str() {
+                Ok(available) => available,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            match available.find('\n') {
+                Some(newline) => {
+                    buf.push_str(&available[..=newline]);
+                    (true, newline + 1)
+                }
+                None => {
+                    buf.push_str(available);
+                    (available.is_empty(), available.len())
+                }
+            }
+        };
+        inner.consume(used);
+        read += used;
+        if done {
+            return Ok(read);
+        }
+    }
+}
+
+/// An iterator over the lines of a [`BufReadStr`], returned by
+/// [`BufReadStr::lines_str`].
+#[derive(Debug)]
+pub struct LinesStr<Inner> {
+    inner: Inner,
+}
+
+impl<Inner: BufReadStr> Iterator for LinesStr<Inner> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        let mut buf = String::new();
+        match self.inner.read_line_str(&mut buf) {
+            Ok(0) => None,
+            Ok(_) => {
+                if buf.ends_with('\n') {
+                    buf.pop();
+                    if buf.ends_with('\r') {
+                        buf.pop();
+                    }
+                }
+                Some(Ok(buf))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
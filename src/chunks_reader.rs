@@ -0,0 +1,192 @@
+use crate::str_reader::{check_not_split, copy_str_prefix, read_prefix_len, str_prefix_len};
+use crate::{BufReadStr, ReadStr};
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+/// A [`ReadStr`] implementation which reads the chunks of text produced by
+/// an iterator, such as an `Iterator<Item = String>`, without re-validating
+/// them.
+///
+/// As with [`StrReader`], a `read` into a buffer too short for the next
+/// scalar value returns its encoding over several `read`s.
+///
+/// [`StrReader`]: crate::StrReader
+pub struct ChunksReader<Iter: Iterator>
+where
+    Iter::Item: AsRef<str>,
+{
+    /// The source of chunks.
+    iter: Iter,
+
+    /// The chunk being read.
+    chunk: Option<Iter::Item>,
+
+    /// The start of the unread part of `chunk`, which is at a `char`
+    /// boundary unless a `read` has split a scalar value's encoding.
+    pos: usize,
+}
+
+impl<Iter: Iterator> ChunksReader<Iter>
+where
+    Iter::Item: AsRef<str>,
+{
+    /// Construct a new instance of `ChunksReader` reading the chunks
+    /// produced by `iter`.
+    #[inline]
+    pub fn new(iter: impl IntoIterator<IntoIter = Iter>) -> Self {
+        Self {
+            iter: iter.into_iter(),
+            chunk: None,
+            pos: 0,
+        }
+    }
+
+    /// Unwrap this `ChunksReader`, returning the iterator. Any unread part of
+    /// the current chunk is lost.
+    #[inline]
+    pub fn into_inner(self) -> Iter {
+        self.iter
+    }
+
+    /// Test whether there is no current chunk or it has been fully read.
+    fn chunk_exhausted(&self) -> bool {
+        match &self.chunk {
+            Some(chunk) => self.pos >= chunk.as_ref().len(),
+            None => true,
+        }
+    }
+
+    /// If the current chunk has been read, move on to the next non-empty
+    /// chunk.
+    fn fill_chunk(&mut self) {
+        // Skip over exhausted and empty chunks.
+        while self.chunk_exhausted() {
+            match self.iter.next() {
+                Some(chunk) => {
+                    self.chunk = Some(chunk);
+                    self.pos = 0;
+                }
+                None => {
+                    self.chunk = None;
+                    self.pos = 0;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Return the current chunk, or `""` at the end.
+    #[inline]
+    fn chunk(&self) -> &str {
+        self.chunk.as_ref().map_or("", AsRef::as_ref)
+    }
+}
+
+impl<Iter: Iterator> BufReadStr for ChunksReader<Iter>
+where
+    Iter::Item: AsRef<str>,
+{
+    fn fill_buf_str(&mut self) -> io::Result<&str> {
+        self.fill_chunk();
+        check_not_split(self.chunk(), self.pos)?;
+        Ok(&self.chunk()[self.pos..])
+    }
+}
+
+impl<Iter: Iterator> BufRead for ChunksReader<Iter>
+where
+    Iter::Item: AsRef<str>,
+{
+    #[inline]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.fill_chunk();
+        Ok(&self.chunk().as_bytes()[self.pos..])
+    }
+
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        if let Some(chunk) = &self.chunk {
+            self.pos = chunk.as_ref().len().min(self.pos + amt);
+        }
+    }
+}
+
+impl<Iter: Iterator> ReadStr for ChunksReader<Iter>
+where
+    Iter::Item: AsRef<str>,
+{
+    #[inline]
+    fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+        let len = copy_str_prefix(self.fill_buf_str()?, buf)?;
+        self.consume(len);
+        Ok(len)
+    }
+
+    #[inline]
+    fn read_str_append(&mut self, buf: &mut String, max: usize) -> io::Result<usize> {
+        let available = self.fill_buf_str()?;
+        let len = str_prefix_len(available, max)?;
+        buf.push_str(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+
+    fn read_str_to_end(&mut self, buf: &mut String) -> io::Result<usize> {
+        let mut read = 0;
+        loop {
+            let available = self.fill_buf_str()?;
+            if available.is_empty() {
+                return Ok(read);
+            }
+            buf.push_str(available);
+            let len = available.len();
+            self.consume(len);
+            read += len;
+        }
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.peek_char()?;
+        if let Some(c) = c {
+            self.consume(c.len_utf8());
+        }
+        Ok(c)
+    }
+
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        Ok(self.fill_buf_str()?.chars().next())
+    }
+}
+
+impl<Iter: Iterator> Read for ChunksReader<Iter>
+where
+    Iter::Item: AsRef<str>,
+{
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill_chunk();
+        let chunk = self.chunk();
+        let len = read_prefix_len(chunk, self.pos, buf.len());
+        buf[..len].copy_from_slice(&chunk.as_bytes()[self.pos..][..len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    #[inline]
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        self.read_str_to_end(buf)
+    }
+}
+
+impl<Iter: Iterator + fmt::Debug> fmt::Debug for ChunksReader<Iter>
+where
+    Iter::Item: AsRef<str>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("ChunksReader");
+        b.field("iter", &self.iter);
+        b.finish()
+    }
+}
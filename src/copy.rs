@@ -1,9 +1,13 @@
-use crate::{ReadStr, WriteStr};
+use crate::read_str::read_str_append_with;
+use crate::{ReadStr, WriteStr, DEFAULT_BUF_SIZE};
 use std::io;
 #[cfg(feature = "layered-io")]
-use {crate::ReadStrLayered, layered_io::Bufferable, std::cmp::max};
-
-const DEFAULT_BUF_SIZE: usize = 8 * 1024;
+use {
+    crate::read_str::{read_from_str_with_status, read_into_spare_capacity},
+    crate::ReadStrLayered,
+    layered_io::{Bufferable, Status},
+    std::cmp::max,
+};
 
 /// Like `std::io::copy`, but for streams that can operate directly on strings,
 /// so we can avoid re-validating them as UTF-8.
@@ -11,18 +15,26 @@ pub fn copy_str<R: ReadStr + ?Sized, W: WriteStr + ?Sized>(
     reader: &mut R,
     writer: &mut W,
 ) -> io::Result<u64> {
-    // TODO: Avoid unnecessary zero-initialization.
-    let mut buf = "\0".repeat(DEFAULT_BUF_SIZE);
+    let mut buf = String::with_capacity(DEFAULT_BUF_SIZE);
+    let mut initialized = 0;
 
     let mut written = 0;
     loop {
-        let len = match reader.read_str(&mut buf) {
+        // The bytes read last time are still initialized once cleared.
+        initialized += buf.len();
+        buf.clear();
+
+        // Safety: `initialized` only counts bytes zeroed or read by a
+        // previous iteration, which are valid UTF-8.
+        let result =
+            unsafe { read_str_append_with(reader, &mut buf, DEFAULT_BUF_SIZE, &mut initialized) };
+        let len = match result {
             Ok(0) => break,
             Ok(nread) => nread,
             Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
             Err(err) => return Err(err),
         };
-        writer.write_str(&buf[..len])?;
+        writer.write_str(&buf)?;
         written += len as u64;
     }
     Ok(written)
@@ -38,16 +50,32 @@ pub fn copy_str_using_status<R: ReadStrLayered + ?Sized, W: WriteStr + Bufferabl
     reader: &mut R,
     writer: &mut W,
 ) -> io::Result<u64> {
-    // TODO: Avoid unnecessary zero-initialization.
-    let mut buf = "\0".repeat(max(
+    let size = max(
         reader.suggested_buffer_size(),
         writer.suggested_buffer_size(),
-    ));
+    );
+    let mut buf = String::with_capacity(size);
+    let mut initialized = 0;
 
     let mut written = 0;
     loop {
-        let (len, status) = reader.read_str_with_status(&mut buf)?;
-        writer.write_str(&buf[..len])?;
+        // The bytes read last time are still initialized once cleared.
+        initialized += buf.len();
+        buf.clear();
+
+        let mut status = Status::active();
+        // Safety: The spare capacity is zeroed before use, and only reused
+        // after `read_str_with_status`, which leaves its buffer valid UTF-8.
+        // `read_from_str_with_status` only returns lengths at `char`
+        // boundaries.
+        let len = unsafe {
+            read_into_spare_capacity(&mut buf, size, &mut initialized, |bytes| {
+                let (len, s) = read_from_str_with_status(reader, bytes)?;
+                status = s;
+                Ok::<_, io::Error>(len)
+            })
+        }?;
+        writer.write_str(&buf)?;
         written += len as u64;
         if status.is_end() {
             return Ok(written);
@@ -0,0 +1,391 @@
+use crate::read_str::read_to_end_with;
+use crate::{ReadStr, Utf8Error, DEFAULT_BUF_SIZE};
+use encoding_rs::{CoderResult, Decoder, Encoding};
+#[cfg(windows)]
+use io_extras::os::windows::{
+    AsHandleOrSocket, AsRawHandleOrSocket, BorrowedHandleOrSocket, RawHandleOrSocket,
+};
+use std::io::{self, Read};
+use std::{fmt, str};
+#[cfg(feature = "terminal-io")]
+use terminal_io::{ReadTerminal, Terminal};
+#[cfg(feature = "layered-io")]
+use {
+    crate::ReadStrLayered,
+    layered_io::{Bufferable, ReadLayered, Status},
+    std::cmp::max,
+};
+#[cfg(not(windows))]
+use {
+    io_extras::os::rustix::{AsRawFd, RawFd},
+    std::os::fd::{AsFd, BorrowedFd},
+};
+
+/// A [`Read`] implementation which decodes an input `Read` producing text in
+/// any encoding supported by [`encoding_rs`] into a valid UTF-8 sequence,
+/// where scalar value encodings never straddle `read` calls (callers can do
+/// [`str::from_utf8`] and it will always succeed).
+///
+/// Malformed sequences are replaced by [U+FFFD (REPLACEMENT CHARACTER)].
+///
+/// [U+FFFD (REPLACEMENT CHARACTER)]: https://util.unicode.org/UnicodeJsps/character.jsp?a=FFFD
+pub struct DecodingReader<Inner: Read> {
+    /// The wrapped byte stream.
+    inner: Inner,
+
+    /// The decoder state.
+    decoder: Decoder,
+
+    /// A buffer for input from `inner`, holding bytes which have been read
+    /// but not decoded yet at `pending_start..pending_end`.
+    pending: Vec<u8>,
+    pending_start: usize,
+    pending_end: usize,
+
+    /// Whether `inner` has reached its end.
+    at_end: bool,
+
+    /// Whether the decoder has been given all of the input and has produced
+    /// all of its output.
+    finished: bool,
+
+    /// Output which has been decoded by `peek_char` or `read_char`, but not
+    /// returned yet, at `peeked_pos..peeked_len`.
+    peeked: [u8; 4],
+    peeked_pos: u8,
+    peeked_len: u8,
+}
+
+impl<Inner: Read> DecodingReader<Inner> {
+    /// Construct a new instance of `DecodingReader` wrapping `inner`, which
+    /// decodes input in `encoding`, unless the input starts with a byte
+    /// order mark identifying UTF-8 or UTF-16, in the manner of
+    /// [`Encoding::new_decoder`].
+    #[inline]
+    pub fn new(inner: Inner, encoding: &'static Encoding) -> Self {
+        Self::with_decoder(inner, encoding.new_decoder())
+    }
+
+    /// Construct a new instance of `DecodingReader` wrapping `inner`, which
+    /// decodes input using `decoder`. This allows byte order mark handling
+    /// to be configured with [`Encoding::new_decoder_with_bom_removal`] or
+    /// [`Encoding::new_decoder_without_bom_handling`].
+    #[inline]
+    pub fn with_decoder(inner: Inner, decoder: Decoder) -> Self {
+        Self {
+            inner,
+            decoder,
+            pending: Vec::new(),
+            pending_start: 0,
+            pending_end: 0,
+            at_end: false,
+            finished: false,
+            peeked: [0; 4],
+            peeked_pos: 0,
+            peeked_len: 0,
+        }
+    }
+
+    /// Return the encoding being decoded. This may change from the encoding
+    /// the reader was constructed with when a byte order mark is found.
+    #[inline]
+    pub fn encoding(&self) -> &'static Encoding {
+        self.decoder.encoding()
+    }
+
+    /// Check that `buf` is long enough to guarantee progress.
+    fn check_buf_len(buf: &[u8]) -> io::Result<()> {
+        // `encoding_rs` needs at least 4 bytes of output space to be able to
+        // make progress.
+        if buf.len() < 4 {
+            return Err(Utf8Error::BufferTooSmall {
+                len: buf.len(),
+                min_len: 4,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Decode as much of `pending` into `buf` as will fit. Return the number
+    /// of bytes written, and whether decoding stopped because `buf` is full.
+    fn decode(&mut self, buf: &mut [u8]) -> (usize, bool) {
+        let (result, read, written, _had_errors) = self.decoder.decode_to_utf8(
+            &self.pending[self.pending_start..self.pending_end],
+            buf,
+            self.at_end,
+        );
+        self.pending_start += read;
+
+        // The decoder may write scratch data after the output; overwrite it.
+        buf[written..].fill(b'\0');
+
+        let full = result == CoderResult::OutputFull;
+        if self.at_end && !full {
+            self.finished = true;
+        }
+        (written, full)
+    }
+
+    /// Return any output decoded by `peek_char` or `read_char` which hasn't
+    /// been returned yet.
+    fn peeked(&self) -> &str {
+        let peeked = &self.peeked[usize::from(self.peeked_pos)..usize::from(self.peeked_len)];
+        // Safety: `peeked` holds complete UTF-8 encodings from `read`.
+        unsafe { str::from_utf8_unchecked(peeked) }
+    }
+
+    /// Return the next `char`, decoding more output if needed.
+    fn fill_peeked(&mut self) -> io::Result<Option<char>> {
+        if self.peeked_pos == self.peeked_len {
+            let mut peeked = [0; 4];
+            let len = loop {
+                match self.read(&mut peeked) {
+                    Ok(len) => break len,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => return Err(err),
+                }
+            };
+            self.peeked = peeked;
+            self.peeked_pos = 0;
+            self.peeked_len = len as u8;
+        }
+        Ok(self.peeked().chars().next())
+    }
+
+    /// Decode any input left over from a previous call. Return the number of
+    /// bytes written, and whether there's output to return without reading
+    /// more input.
+    fn process_old_data(&mut self, buf: &mut [u8]) -> io::Result<(usize, bool)> {
+        Self::check_buf_len(buf)?;
+
+        // Return output decoded by `peek_char` or `read_char` first. It's at
+        // most 4 bytes, so it always fits.
+        let peeked_len = self.peeked().len();
+        if peeked_len != 0 {
+            buf[..peeked_len].copy_from_slice(self.peeked().as_bytes());
+            self.peeked_pos = self.peeked_len;
+            return Ok((peeked_len, true));
+        }
+
+        if self.finished {
+            return Ok((0, true));
+        }
+        if self.pending_start == self.pending_end && !self.at_end {
+            return Ok((0, false));
+        }
+
+        let (nread, full) = self.decode(buf);
+        Ok((nread, full || nread != 0 || self.finished))
+    }
+
+    /// Prepare `pending` to receive up to `len` new bytes from `inner` at
+    /// `pending_end`.
+    fn reserve_pending(&mut self, len: usize) {
+        // Move any bytes which haven't been decoded yet to the front, if
+        // that makes room.
+        if self.pending_end + len > self.pending.len() && self.pending_start != 0 {
+            self.pending
+                .copy_within(self.pending_start..self.pending_end, 0);
+            self.pending_end -= self.pending_start;
+            self.pending_start = 0;
+        }
+        if self.pending_end + len > self.pending.len() {
+            self.pending.resize(self.pending_end + len, 0);
+        }
+    }
+
+    /// Decode `size` bytes newly read from `inner` into `pending` at
+    /// `pending_end`. Return the number of bytes written to `buf`.
+    fn process_new_data(&mut self, buf: &mut [u8], size: usize, is_end: bool) -> usize {
+        self.pending_end += size;
+        self.at_end = is_end;
+        self.decode(buf).0
+    }
+}
+
+#[cfg(feature = "terminal-io")]
+impl<Inner: Read + ReadTerminal> Terminal for DecodingReader<Inner> {}
+
+#[cfg(feature = "terminal-io")]
+impl<Inner: Read + ReadTerminal> ReadTerminal for DecodingReader<Inner> {
+    #[inline]
+    fn is_line_by_line(&self) -> bool {
+        self.inner.is_line_by_line()
+    }
+
+    #[inline]
+    fn is_input_terminal(&self) -> bool {
+        self.inner.is_input_terminal()
+    }
+}
+
+#[cfg(feature = "layered-io")]
+impl<Inner: ReadLayered> ReadLayered for DecodingReader<Inner> {
+    fn read_with_status(&mut self, buf: &mut [u8]) -> io::Result<(usize, Status)> {
+        let (nread, done) = self.process_old_data(buf)?;
+        if done {
+            let status = if self.finished {
+                Status::End
+            } else {
+                Status::active()
+            };
+            return Ok((nread, status));
+        }
+
+        self.reserve_pending(buf.len());
+        let pending = &mut self.pending[self.pending_end..][..buf.len()];
+        let (size, status) = self.inner.read_with_status(pending)?;
+
+        let nread = self.process_new_data(buf, size, status.is_end());
+        let done = self.finished || (self.pending_start == self.pending_end && !status.is_end());
+        Ok((nread, if done { status } else { Status::active() }))
+    }
+
+    #[inline]
+    fn minimum_buffer_size(&self) -> usize {
+        max(4, self.inner.minimum_buffer_size())
+    }
+}
+
+#[cfg(feature = "layered-io")]
+impl<Inner: ReadLayered> Bufferable for DecodingReader<Inner> {
+    #[inline]
+    fn abandon(&mut self) {
+        self.pending_start = 0;
+        self.pending_end = 0;
+        self.peeked_len = self.peeked_pos;
+        self.inner.abandon()
+    }
+
+    #[inline]
+    fn suggested_buffer_size(&self) -> usize {
+        max(
+            self.minimum_buffer_size(),
+            self.inner.suggested_buffer_size(),
+        )
+    }
+}
+
+impl<Inner: Read> ReadStr for DecodingReader<Inner> {
+    #[inline]
+    fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
+        self.read(unsafe { buf.as_bytes_mut() })
+    }
+
+    #[inline]
+    fn read_exact_str(&mut self, buf: &mut str) -> io::Result<()> {
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
+        self.read_exact(unsafe { buf.as_bytes_mut() })
+    }
+
+    #[inline]
+    fn read_str_to_end(&mut self, buf: &mut String) -> io::Result<usize> {
+        // Safety: Our `read` implementation only produces complete UTF-8
+        // encodings.
+        unsafe { read_to_end_with(buf, DEFAULT_BUF_SIZE, |bytes| self.read(bytes)) }
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.fill_peeked()?;
+        if let Some(c) = c {
+            self.peeked_pos += c.len_utf8() as u8;
+        }
+        Ok(c)
+    }
+
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        self.fill_peeked()
+    }
+}
+
+#[cfg(feature = "layered-io")]
+impl<Inner: ReadLayered> ReadStrLayered for DecodingReader<Inner> {
+    #[inline]
+    fn read_str_with_status(&mut self, buf: &mut str) -> io::Result<(usize, Status)> {
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
+        self.read_with_status(unsafe { buf.as_bytes_mut() })
+    }
+
+    #[inline]
+    fn read_exact_str_using_status(&mut self, buf: &mut str) -> io::Result<Status> {
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
+        self.read_exact_using_status(unsafe { buf.as_bytes_mut() })
+    }
+}
+
+impl<Inner: Read> Read for DecodingReader<Inner> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (nread, done) = self.process_old_data(buf)?;
+        if done {
+            return Ok(nread);
+        }
+
+        self.reserve_pending(buf.len());
+        let pending = &mut self.pending[self.pending_end..][..buf.len()];
+        let (size, is_end) = match self.inner.read(pending) {
+            Ok(0) => (0, true),
+            Ok(size) => (size, false),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (0, false),
+            Err(err) => return Err(err),
+        };
+
+        match self.process_new_data(buf, size, is_end) {
+            0 if self.finished => Ok(0),
+            0 => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "read zero bytes from stream",
+            )),
+            nread => Ok(nread),
+        }
+    }
+
+    #[inline]
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        self.read_str_to_end(buf)
+    }
+}
+
+#[cfg(not(windows))]
+impl<Inner: Read + AsRawFd> AsRawFd for DecodingReader<Inner> {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(not(windows))]
+impl<Inner: Read + AsFd> AsFd for DecodingReader<Inner> {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+#[cfg(windows)]
+impl<Inner: Read + AsRawHandleOrSocket> AsRawHandleOrSocket for DecodingReader<Inner> {
+    #[inline]
+    fn as_raw_handle_or_socket(&self) -> RawHandleOrSocket {
+        self.inner.as_raw_handle_or_socket()
+    }
+}
+
+#[cfg(windows)]
+impl<Inner: Read + AsHandleOrSocket> AsHandleOrSocket for DecodingReader<Inner> {
+    #[inline]
+    fn as_handle_or_socket(&self) -> BorrowedHandleOrSocket<'_> {
+        self.inner.as_handle_or_socket()
+    }
+}
+
+impl<Inner: Read + fmt::Debug> fmt::Debug for DecodingReader<Inner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("DecodingReader");
+        b.field("inner", &self.inner);
+        b.field("encoding", &self.encoding());
+        b.finish()
+    }
+}
@@ -0,0 +1,125 @@
+/// A Unicode encoding scheme, which a reader can detect from a leading byte
+/// order mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// UTF-8.
+    #[default]
+    Utf8,
+
+    /// UTF-16, little-endian.
+    Utf16Le,
+
+    /// UTF-16, big-endian.
+    Utf16Be,
+
+    /// UTF-32, little-endian.
+    Utf32Le,
+
+    /// UTF-32, big-endian.
+    Utf32Be,
+}
+
+/// The result of decoding the start of some UTF-16 or UTF-32 input.
+pub(crate) enum Decoded {
+    /// A scalar value, and the length of its encoding.
+    Char(char, usize),
+
+    /// An invalid sequence of the given length, such as an unpaired
+    /// surrogate.
+    Invalid(usize),
+
+    /// The input ends partway through an encoding.
+    Incomplete,
+}
+
+impl Encoding {
+    /// The byte order marks which identify each encoding, longest first, so
+    /// that a UTF-32LE byte order mark isn't mistaken for a UTF-16LE one.
+    pub(crate) const BOMS: [(&'static [u8], Self); 5] = [
+        (b"\xFF\xFE\0\0", Self::Utf32Le),
+        (b"\0\0\xFE\xFF", Self::Utf32Be),
+        (b"\xEF\xBB\xBF", Self::Utf8),
+        (b"\xFE\xFF", Self::Utf16Be),
+        (b"\xFF\xFE", Self::Utf16Le),
+    ];
+
+    /// Decode a scalar value from the start of `bytes`, which must not be in
+    /// UTF-8, which is validated in place instead.
+    pub(crate) fn decode(self, bytes: &[u8]) -> Decoded {
+        match self {
+            Self::Utf8 => unreachable!("UTF-8 input is not transcoded"),
+            Self::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Self::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Self::Utf32Le => decode_utf32(bytes, u32::from_le_bytes),
+            Self::Utf32Be => decode_utf32(bytes, u32::from_be_bytes),
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Decoded {
+    let unit = |i: usize| from_bytes([bytes[i], bytes[i + 1]]);
+
+    if bytes.len() < 2 {
+        return Decoded::Incomplete;
+    }
+    match unit(0) {
+        high @ 0xd800..=0xdbff => {
+            if bytes.len() < 4 {
+                return Decoded::Incomplete;
+            }
+            match unit(2) {
+                low @ 0xdc00..=0xdfff => {
+                    let c =
+                        0x1_0000 + ((u32::from(high) - 0xd800) << 10) + (u32::from(low) - 0xdc00);
+                    Decoded::Char(char::from_u32(c).unwrap(), 4)
+                }
+                _ => Decoded::Invalid(2),
+            }
+        }
+        0xdc00..=0xdfff => Decoded::Invalid(2),
+        unit => Decoded::Char(char::from_u32(u32::from(unit)).unwrap(), 2),
+    }
+}
+
+fn decode_utf32(bytes: &[u8], from_bytes: fn([u8; 4]) -> u32) -> Decoded {
+    if bytes.len() < 4 {
+        return Decoded::Incomplete;
+    }
+    match char::from_u32(from_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])) {
+        Some(c) => Decoded::Char(c, 4),
+        None => Decoded::Invalid(4),
+    }
+}
+
+/// Decode a CESU-8 surrogate pair or Modified UTF-8's encoding of U+0000
+/// from the start of `bytes`. Returns `Decoded::Invalid` if it starts with
+/// neither, leaving other decoding to the caller.
+pub(crate) fn decode_modified_utf8(bytes: &[u8]) -> Decoded {
+    const PATTERN: [(u8, u8); 6] = [
+        (0xed, 0xed),
+        (0xa0, 0xaf),
+        (0x80, 0xbf),
+        (0xed, 0xed),
+        (0xb0, 0xbf),
+        (0x80, 0xbf),
+    ];
+
+    if bytes.first() == Some(&0xc0) {
+        return match bytes.get(1) {
+            Some(0x80) => Decoded::Char('\0', 2),
+            Some(_) => Decoded::Invalid(1),
+            None => Decoded::Incomplete,
+        };
+    }
+
+    for (i, &(lo, hi)) in PATTERN.iter().enumerate() {
+        match bytes.get(i) {
+            Some(byte) if (lo..=hi).contains(byte) => {}
+            Some(_) => return Decoded::Invalid(1),
+            None => return Decoded::Incomplete,
+        }
+    }
+    let high = (u32::from(bytes[1] & 0x0f) << 6) | u32::from(bytes[2] & 0x3f);
+    let low = (u32::from(bytes[4] & 0x0f) << 6) | u32::from(bytes[5] & 0x3f);
+    Decoded::Char(char::from_u32(0x1_0000 + (high << 10) + low).unwrap(), 6)
+}
@@ -0,0 +1,78 @@
+use crate::WriteStr;
+use std::fmt;
+use std::io;
+
+/// An adaptor which implements [`fmt::Write`] for a [`WriteStr`]
+/// implementation, so that `write!` can be used with it.
+///
+/// `fmt::Write` can only report a [`fmt::Error`], so the underlying
+/// [`io::Error`] is saved, and can be recovered with
+/// [`FmtAdaptor::io_result`] or [`FmtAdaptor::take_error`].
+pub struct FmtAdaptor<Inner> {
+    /// The wrapped stream.
+    inner: Inner,
+
+    /// The most recent error from `inner`.
+    error: Option<io::Error>,
+}
+
+impl<Inner: WriteStr> FmtAdaptor<Inner> {
+    /// Construct a new instance of `FmtAdaptor` wrapping `inner`.
+    #[inline]
+    pub fn new(inner: Inner) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Return the error which caused the most recent [`fmt::Error`], if
+    /// any, and clear it.
+    #[inline]
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Convert the result of a formatting operation into an [`io::Result`],
+    /// reporting the underlying error if there was one.
+    pub fn io_result(&mut self, result: fmt::Result) -> io::Result<()> {
+        let error = self.error.take();
+        match result {
+            Ok(()) => Ok(()),
+            Err(fmt::Error) => Err(error.unwrap_or_else(|| io::Error::other("formatter error"))),
+        }
+    }
+
+    /// Return a reference to the wrapped stream.
+    #[inline]
+    pub fn get_ref(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Return a mutable reference to the wrapped stream.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    /// Unwrap this `FmtAdaptor`, returning the wrapped stream.
+    #[inline]
+    pub fn into_inner(self) -> Inner {
+        self.inner
+    }
+}
+
+impl<Inner: WriteStr> fmt::Write for FmtAdaptor<Inner> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_str(s).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+impl<Inner: fmt::Debug> fmt::Debug for FmtAdaptor<Inner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("FmtAdaptor");
+        b.field("inner", &self.inner);
+        b.finish()
+    }
+}
@@ -0,0 +1,166 @@
+use crate::invalid::invalid_output;
+use crate::{default_write_fmt, Utf8Error, Utf8Validator, WriteStr};
+use std::fmt::{self, Arguments};
+use std::io::{self, Write};
+use std::str;
+
+/// An adaptor which implements [`WriteStr`] and [`Write`] for a
+/// [`fmt::Write`] implementation, such as a `String` or a
+/// [`fmt::Formatter`], so that UTF-8 streams can be copied into it.
+///
+/// Bytes written with `write` are validated, and may split a scalar value's
+/// encoding across calls. Attempts to write invalid encodings are reported
+/// as errors.
+pub struct FmtWriteStr<Inner: fmt::Write> {
+    /// The wrapped formatting sink.
+    inner: Inner,
+
+    /// Validation state for bytes written with `write`, holding the start of
+    /// a scalar value's encoding which hasn't been completed yet.
+    validator: Utf8Validator,
+
+    /// The number of bytes written so far, not counting any incomplete
+    /// encoding held by `validator`, which is the offset of any incomplete or
+    /// invalid encoding which follows.
+    written: u64,
+}
+
+impl<Inner: fmt::Write> FmtWriteStr<Inner> {
+    /// Construct a new instance of `FmtWriteStr` wrapping `inner`.
+    #[inline]
+    pub fn new(inner: Inner) -> Self {
+        Self {
+            inner,
+            validator: Utf8Validator::new(),
+            written: 0,
+        }
+    }
+
+    /// Return a reference to the wrapped sink.
+    #[inline]
+    pub fn get_ref(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Return a mutable reference to the wrapped sink.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    /// Check that no incomplete encoding is pending and return the wrapped
+    /// sink.
+    #[inline]
+    pub fn into_inner(mut self) -> io::Result<Inner> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Write `s` to the wrapped sink.
+    #[inline]
+    fn write_to_inner(&mut self, s: &str) -> io::Result<()> {
+        self.inner
+            .write_str(s)
+            .map_err(|fmt::Error| io::Error::other("formatter error"))?;
+        self.written += s.len() as u64;
+        Ok(())
+    }
+
+    /// Return the error for an incomplete encoding which is still pending.
+    #[cold]
+    fn incomplete_error(&self) -> io::Error {
+        Utf8Error::IncompleteAtFlush {
+            offset: self.written,
+            bytes: self.validator.incomplete().to_vec(),
+        }
+        .into()
+    }
+
+    /// Return the error for a `write_str` while an incomplete encoding is
+    /// pending.
+    #[cold]
+    fn write_str_error(&self) -> io::Error {
+        Utf8Error::IncompleteAtWriteStr {
+            offset: self.written,
+            bytes: self.validator.incomplete().to_vec(),
+        }
+        .into()
+    }
+}
+
+impl<Inner: fmt::Write> WriteStr for FmtWriteStr<Inner> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        if !self.validator.incomplete().is_empty() {
+            return Err(self.write_str_error());
+        }
+        self.write_to_inner(s)
+    }
+}
+
+impl<Inner: fmt::Write> Write for FmtWriteStr<Inner> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // If we have incomplete bytes from the previous `write`, try to
+        // complete them.
+        if !self.validator.incomplete().is_empty() {
+            let (copy_len, completed) = self.validator.complete(buf);
+
+            // If the sequence is still incomplete, wait for the next `write`.
+            let (incomplete, utf8_len) = match completed {
+                Some(completed) => completed,
+                None => return Ok(copy_len),
+            };
+
+            let s = str::from_utf8(&incomplete[..utf8_len]).map_err(|error| {
+                invalid_output(self.written, &incomplete[..error.error_len().unwrap()])
+            })?;
+            self.write_to_inner(s)?;
+            return Ok(copy_len);
+        }
+
+        // If the buffer is UTF-8, write it. If it has incomplete bytes at the
+        // end, write what we can and save the incomplete bytes for the next
+        // `write`. If it's invalid, write what we can and fail.
+        match str::from_utf8(buf) {
+            Ok(s) => self.write_to_inner(s).map(|()| buf.len()),
+            Err(error) => {
+                let valid_up_to = error.valid_up_to();
+                if valid_up_to != 0 {
+                    // Safety: `from_utf8` validated this part of `buf`.
+                    let valid = unsafe { str::from_utf8_unchecked(&buf[..valid_up_to]) };
+                    self.write_to_inner(valid)?;
+                }
+                if error.error_len().is_none() {
+                    self.validator.hold(&buf[valid_up_to..]);
+                    Ok(buf.len())
+                } else {
+                    let invalid = &buf[valid_up_to..][..error.error_len().unwrap()];
+                    Err(invalid_output(self.written, invalid))
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.validator.incomplete().is_empty() {
+            let error = self.incomplete_error();
+            self.validator.take_incomplete();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, fmt: Arguments) -> io::Result<()> {
+        default_write_fmt(self, fmt)
+    }
+}
+
+impl<Inner: fmt::Write + fmt::Debug> fmt::Debug for FmtWriteStr<Inner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("FmtWriteStr");
+        b.field("inner", &self.inner);
+        b.finish()
+    }
+}
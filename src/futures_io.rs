@@ -0,0 +1,79 @@
+//! Async traits and functions for UTF-8 I/O with [`futures-io`].
+//!
+//! [`futures-io`]: https://docs.rs/futures-io
+
+use crate::async_str::{copy_str_with, impl_forwarding_async_str, write_str_with};
+use ::futures_io::{AsyncRead, AsyncWrite};
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Extend futures-io's `AsyncRead` trait with `poll_read_str`, a method for
+/// reading UTF-8 data.
+pub trait AsyncReadStr: AsyncRead {
+    /// Like `poll_read` but produces the result in a `str`, returning the
+    /// number of bytes written. The rest of `buf` may be overwritten.
+    ///
+    /// `buf` must be at least 4 bytes long, so that any valid UTF-8
+    /// codepoint can be read. Implementations which substitute replacement
+    /// text for invalid input may require it to be long enough for the
+    /// replacement.
+    fn poll_read_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut str,
+    ) -> Poll<io::Result<usize>>;
+}
+
+/// Extend futures-io's `AsyncWrite` trait with `poll_write_str`, a method for
+/// writing UTF-8 data.
+pub trait AsyncWriteStr: AsyncWrite {
+    /// Like `poll_write` but takes a `str`, and only ever reports a number
+    /// of bytes written which is at a `char` boundary of `s`.
+    fn poll_write_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        s: &str,
+    ) -> Poll<io::Result<usize>>;
+}
+
+impl_forwarding_async_str!();
+
+/// Read from `reader` into `buf`, returning the number of bytes read.
+#[inline]
+pub async fn read_str<R: AsyncReadStr + Unpin + ?Sized>(
+    reader: &mut R,
+    buf: &mut str,
+) -> io::Result<usize> {
+    poll_fn(|cx| Pin::new(&mut *reader).poll_read_str(cx, buf)).await
+}
+
+/// Write all of `s` to `writer`.
+///
+/// If this future is dropped before it completes, a prefix of `s` ending at
+/// a `char` boundary has been written.
+pub async fn write_str<W: AsyncWriteStr + Unpin + ?Sized>(
+    writer: &mut W,
+    s: &str,
+) -> io::Result<()> {
+    write_str_with(s, |cx, s| Pin::new(&mut *writer).poll_write_str(cx, s)).await
+}
+
+/// Like `futures::io::copy`, but for streams that can operate directly on
+/// strings, so we can avoid re-validating them as UTF-8.
+///
+/// Data which has been read but not yet written is lost if this future is
+/// dropped before it completes.
+pub async fn copy_str<R: AsyncReadStr + Unpin + ?Sized, W: AsyncWriteStr + Unpin + ?Sized>(
+    reader: &mut R,
+    writer: &mut W,
+) -> io::Result<u64> {
+    let written = copy_str_with(
+        |cx, buf| Pin::new(&mut *reader).poll_read_str(cx, buf),
+        |cx, s| Pin::new(&mut *writer).poll_write_str(cx, s),
+    )
+    .await?;
+    poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await?;
+    Ok(written)
+}
@@ -0,0 +1,293 @@
+use crate::utf8_error::write_bytes;
+#[cfg(feature = "std")]
+use crate::Utf8Error;
+use alloc::vec::Vec;
+use core::{error, fmt, str};
+#[cfg(feature = "std")]
+use std::io;
+
+/// What a reader should do when it encounters byte sequences which are not
+/// valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidPolicy {
+    /// Replace invalid sequences with [U+FFFD (REPLACEMENT CHARACTER)], in
+    /// the manner of [`String::from_utf8_lossy`], or with another
+    /// [`Replacement`].
+    ///
+    /// [U+FFFD (REPLACEMENT CHARACTER)]: https://util.unicode.org/UnicodeJsps/character.jsp?a=FFFD
+    #[default]
+    Replace,
+
+    /// Report invalid sequences as [`io::ErrorKind::InvalidData`] errors.
+    ///
+    /// All valid data preceding an invalid sequence is returned before the
+    /// error is reported. The error's inner error is a
+    /// [`Utf8Error::InvalidSequence`] holding the offending bytes, which are
+    /// consumed, so reading may continue after the error.
+    ///
+    /// [`io::ErrorKind::InvalidData`]: std::io::ErrorKind::InvalidData
+    /// [`Utf8Error::InvalidSequence`]: crate::Utf8Error::InvalidSequence
+    Error,
+
+    /// Silently discard invalid sequences.
+    Skip,
+
+    /// Losslessly escape each invalid byte as a code point in the range
+    /// U+10FF80 through U+10FFFF, in the Supplementary Private Use Area-B,
+    /// by adding the byte's value to U+10FF00, in the manner of [PEP 383].
+    ///
+    /// Valid encodings of code points in that range in the input are escaped
+    /// too, so that a [`Utf8Writer`] with [`Utf8Writer::unescape`] enabled
+    /// can reproduce the original input byte for byte. As they can't be
+    /// represented otherwise, they are also reported as invalid sequences.
+    ///
+    /// [PEP 383]: https://peps.python.org/pep-0383/
+    /// [`Utf8Writer`]: crate::Utf8Writer
+    /// [`Utf8Writer::unescape`]: crate::Utf8Writer::unescape
+    Escape,
+
+    /// Decode each byte of an invalid sequence as [Windows-1252], as
+    /// browsers and many editors do for mislabeled files.
+    ///
+    /// Bytes which Windows-1252 leaves undefined are decoded as the C1
+    /// control with the same value, following the WHATWG Encoding Standard.
+    ///
+    /// [Windows-1252]: https://encoding.spec.whatwg.org/index-windows-1252.txt
+    Windows1252,
+
+    /// Decode each byte of an invalid sequence as Latin-1 (ISO-8859-1),
+    /// mapping each byte to the code point with the same value.
+    Latin1,
+}
+
+impl InvalidPolicy {
+    /// Test whether this policy handles invalid sequences a byte at a time.
+    #[inline]
+    pub(crate) fn is_per_byte(self) -> bool {
+        matches!(self, Self::Escape | Self::Windows1252 | Self::Latin1)
+    }
+
+    /// For policies which translate invalid sequences a byte at a time,
+    /// return the translation of `byte`.
+    #[inline]
+    pub(crate) fn translate_byte(self, byte: u8) -> Option<char> {
+        match self {
+            Self::Escape => Some(escape_byte(byte)),
+            Self::Windows1252 => Some(decode_windows_1252(byte)),
+            Self::Latin1 => Some(char::from(byte)),
+            Self::Replace | Self::Error | Self::Skip => None,
+        }
+    }
+}
+
+/// Decode `byte` as Windows-1252.
+fn decode_windows_1252(byte: u8) -> char {
+    const C1: [char; 32] = [
+        '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}',
+        '\u{2021}', '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}',
+        '\u{8f}', '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}',
+        '\u{2014}', '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}',
+        '\u{178}',
+    ];
+    match byte {
+        0x80..=0x9f => C1[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// The code point which `InvalidPolicy::Escape` adds invalid bytes to.
+const ESCAPE_BASE: u32 = 0x10_ff00;
+
+/// Escape an invalid byte for `InvalidPolicy::Escape`.
+#[inline]
+pub(crate) fn escape_byte(byte: u8) -> char {
+    debug_assert!(byte >= 0x80);
+    char::from_u32(ESCAPE_BASE + u32::from(byte)).unwrap()
+}
+
+/// If `c` is an escape produced by `InvalidPolicy::Escape`, return the byte
+/// it represents.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn unescape_char(c: char) -> Option<u8> {
+    match u32::from(c) {
+        code @ 0x10_ff80..=0x10_ffff => Some((code - ESCAPE_BASE) as u8),
+        _ => None,
+    }
+}
+
+/// Find the first valid encoding of an escape code point in `valid`, which
+/// must be valid UTF-8.
+#[inline]
+pub(crate) fn find_escape(valid: &[u8]) -> Option<usize> {
+    // U+10FF80 through U+10FFFF are encoded as F4 8F BE 80 through
+    // F4 8F BF BF.
+    valid
+        .windows(3)
+        .position(|w| w[0] == 0xf4 && w[1] == 0x8f && w[2] >= 0xbe)
+}
+
+/// How many replacements a reader produces for an invalid sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Granularity {
+    /// Produce one replacement for each maximal subpart of an ill-formed
+    /// sequence, as [`str::from_utf8`]'s [`Utf8Error::error_len`] reports,
+    /// following the WHATWG Encoding Standard and the Unicode Standard's
+    /// recommended practice. This is what [`String::from_utf8_lossy`] does.
+    ///
+    /// [`Utf8Error::error_len`]: core::str::Utf8Error::error_len
+    #[default]
+    MaximalSubpart,
+
+    /// Produce one replacement for each invalid byte, so that the length of
+    /// the output can be related to the length of the input.
+    ///
+    /// Each invalid byte is also reported as a separate [`InvalidSequence`].
+    PerByte,
+}
+
+/// The text a reader substitutes for invalid sequences when using
+/// [`InvalidPolicy::Replace`].
+///
+/// This can be constructed from a `char` or a `&'static str`. The default is
+/// [U+FFFD (REPLACEMENT CHARACTER)].
+///
+/// [U+FFFD (REPLACEMENT CHARACTER)]: https://util.unicode.org/UnicodeJsps/character.jsp?a=FFFD
+#[derive(Debug, Clone, Copy)]
+pub struct Replacement {
+    repr: ReplacementRepr,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ReplacementRepr {
+    /// A UTF-8 encoded `char`, and its length.
+    Char([u8; 4], u8),
+    Str(&'static str),
+}
+
+impl Replacement {
+    /// U+FFFD (REPLACEMENT CHARACTER).
+    pub(crate) const REPLACEMENT_CHARACTER: Self = Self {
+        repr: ReplacementRepr::Str("\u{fffd}"),
+    };
+
+    /// Return the replacement text.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match &self.repr {
+            // Safety: `bytes` holds the UTF-8 encoding of a `char`.
+            ReplacementRepr::Char(bytes, len) => unsafe {
+                str::from_utf8_unchecked(&bytes[..usize::from(*len)])
+            },
+            ReplacementRepr::Str(s) => s,
+        }
+    }
+}
+
+impl Default for Replacement {
+    #[inline]
+    fn default() -> Self {
+        Self::REPLACEMENT_CHARACTER
+    }
+}
+
+impl PartialEq for Replacement {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Replacement {}
+
+impl From<char> for Replacement {
+    #[inline]
+    fn from(c: char) -> Self {
+        let mut bytes = [0; 4];
+        let len = c.encode_utf8(&mut bytes).len() as u8;
+        Self {
+            repr: ReplacementRepr::Char(bytes, len),
+        }
+    }
+}
+
+impl From<&'static str> for Replacement {
+    #[inline]
+    fn from(s: &'static str) -> Self {
+        Self {
+            repr: ReplacementRepr::Str(s),
+        }
+    }
+}
+
+/// A byte sequence which is not valid UTF-8.
+///
+/// This is passed to `on_invalid` callbacks, such as the one registered with
+/// [`Utf8Reader::on_invalid`], and is held by the [`Utf8Error`] reported by
+/// readers using [`InvalidPolicy::Error`] and by writers given invalid input.
+///
+/// [`Utf8Reader::on_invalid`]: crate::Utf8Reader::on_invalid
+/// [`Utf8Error`]: crate::Utf8Error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSequence {
+    pub(crate) offset: u64,
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) truncated: bool,
+}
+
+impl InvalidSequence {
+    /// Return the offset of the invalid sequence in the input stream, in
+    /// bytes.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the bytes of the invalid sequence.
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Test whether the sequence is invalid because it is the start of an
+    /// encoding which was truncated by the end of the stream, rather than
+    /// being invalid in the middle of the stream.
+    #[inline]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl fmt::Display for InvalidSequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.truncated {
+            write!(f, "truncated UTF-8 sequence at byte {}:", self.offset)?;
+        } else {
+            write!(f, "invalid UTF-8 sequence at byte {}:", self.offset)?;
+        }
+        write_bytes(f, &self.bytes)
+    }
+}
+
+impl error::Error for InvalidSequence {}
+
+/// Construct the error writers report for the invalid sequence `bytes`,
+/// found at `offset` in the stream of bytes written.
+#[cfg(feature = "std")]
+#[cold]
+pub(crate) fn invalid_output(offset: u64, bytes: &[u8]) -> io::Error {
+    InvalidSequence {
+        offset,
+        bytes: bytes.to_vec(),
+        truncated: false,
+    }
+    .into()
+}
+
+#[cfg(feature = "std")]
+impl From<InvalidSequence> for io::Error {
+    #[inline]
+    fn from(invalid: InvalidSequence) -> Self {
+        Utf8Error::InvalidSequence(invalid).into()
+    }
+}
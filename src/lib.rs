@@ -1,23 +1,108 @@
 //! Traits and types for UTF-8 I/O
 
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_str;
+#[cfg(feature = "std")]
+mod buf_read_str;
+#[cfg(feature = "std")]
+mod chunks_reader;
+#[cfg(feature = "std")]
 mod copy;
+#[cfg(feature = "encoding_rs")]
+mod decoding_reader;
+mod encoding;
+#[cfg(feature = "std")]
+mod fmt_adaptor;
+#[cfg(feature = "std")]
+mod fmt_write_str;
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+mod invalid;
+#[cfg(feature = "std")]
 mod read_str;
+#[cfg(feature = "std")]
+mod str_reader;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod utf8_async_duplexer;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod utf8_async_output;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod utf8_async_reader;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod utf8_async_writer;
+#[cfg(feature = "std")]
+mod utf8_buf_reader;
+mod utf8_decoder;
+#[cfg(feature = "std")]
 mod utf8_duplexer;
+mod utf8_error;
+#[cfg(feature = "std")]
 mod utf8_input;
+#[cfg(feature = "std")]
 mod utf8_output;
+#[cfg(feature = "std")]
 mod utf8_reader;
+mod utf8_validator;
+#[cfg(feature = "std")]
 mod utf8_writer;
+#[cfg(feature = "std")]
 mod write_str;
 
+#[cfg(feature = "std")]
+pub use buf_read_str::{default_read_line_str, BufReadStr, LinesStr};
+#[cfg(feature = "std")]
+pub use chunks_reader::ChunksReader;
+#[cfg(feature = "std")]
 pub use copy::copy_str;
 #[cfg(feature = "layered-io")]
 pub use copy::copy_str_using_status;
+#[cfg(feature = "encoding_rs")]
+pub use decoding_reader::DecodingReader;
+pub use encoding::Encoding;
+#[cfg(feature = "std")]
+pub use fmt_adaptor::FmtAdaptor;
+#[cfg(feature = "std")]
+pub use fmt_write_str::FmtWriteStr;
+pub use invalid::{Granularity, InvalidPolicy, InvalidSequence, Replacement};
 #[cfg(feature = "layered-io")]
 pub use read_str::ReadStrLayered;
-pub use read_str::{default_read_exact_str, ReadStr};
+#[cfg(feature = "std")]
+pub use read_str::{
+    default_read_char, default_read_exact_str, default_read_str_append, default_read_str_to_end,
+    Chars, ReadStr,
+};
+#[cfg(feature = "std")]
+pub use str_reader::StrReader;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use utf8_async_duplexer::Utf8AsyncDuplexer;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use utf8_async_reader::Utf8AsyncReader;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use utf8_async_writer::Utf8AsyncWriter;
+#[cfg(feature = "std")]
+pub use utf8_buf_reader::Utf8BufReader;
+pub use utf8_decoder::Utf8Decoder;
+#[cfg(feature = "std")]
 pub use utf8_duplexer::Utf8Duplexer;
+pub use utf8_error::Utf8Error;
+#[cfg(feature = "std")]
 pub use utf8_reader::Utf8Reader;
+pub use utf8_validator::Utf8Validator;
+#[cfg(feature = "std")]
 pub use utf8_writer::Utf8Writer;
-pub use write_str::{default_write_fmt, default_write_str, WriteStr};
+#[cfg(feature = "std")]
+pub use write_str::{
+    default_write_fmt, default_write_str, default_write_str_partial, default_write_str_vectored,
+    WriteStr,
+};
+
+/// The default size of buffers used for reading.
+#[cfg(feature = "std")]
+pub(crate) const DEFAULT_BUF_SIZE: usize = 8 * 1024;
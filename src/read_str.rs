@@ -1,14 +1,22 @@
+use crate::DEFAULT_BUF_SIZE;
 #[cfg(feature = "layered-io")]
 use layered_io::{ReadLayered, Status};
 use std::io::{self, Read};
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+use std::task::Poll;
+use std::{slice, str};
 
 /// Extend the `Read` trait with `read_str`, a method for reading UTF-8 data.
 pub trait ReadStr: Read {
     /// Like `read` but produces the result in a `str`. Be sure to check the
     /// `size` field of the return value to see how many bytes were written.
     ///
-    /// `buf` must be at least 4 bytes long, so that any valid UTF-8 codepoint
-    /// can be read.
+    /// `buf` should be at least 4 bytes long, so that any valid UTF-8
+    /// codepoint can be read. Implementations which substitute replacement
+    /// text for invalid input may require it to be long enough for the
+    /// replacement. Implementations which accept shorter buffers report an
+    /// [`io::ErrorKind::InvalidInput`] error if the next scalar value doesn't
+    /// fit.
     fn read_str(&mut self, buf: &mut str) -> io::Result<usize>;
 
     /// Like `read_exact` but produces the result in a `str`.
@@ -16,6 +24,83 @@ pub trait ReadStr: Read {
     fn read_exact_str(&mut self, buf: &mut str) -> io::Result<()> {
         default_read_exact_str(self, buf)
     }
+
+    /// Read up to `max` bytes, appending them to `buf`, and return the
+    /// number of bytes appended. Unlike `read_str`, this doesn't require the
+    /// caller to initialize a buffer first.
+    ///
+    /// `max` must be at least 4, as with the length of the buffer passed to
+    /// `read_str`. If an error occurs, `buf` is left unchanged.
+    #[inline]
+    fn read_str_append(&mut self, buf: &mut String, max: usize) -> io::Result<usize> {
+        default_read_str_append(self, buf, max)
+    }
+
+    /// Like `read_to_string`, but avoids re-validating the data as UTF-8.
+    ///
+    /// If an error occurs, `buf` keeps everything which was read before it.
+    #[inline]
+    fn read_str_to_end(&mut self, buf: &mut String) -> io::Result<usize> {
+        default_read_str_to_end(self, buf)
+    }
+
+    /// Read a single `char`, returning `None` at the end of the stream.
+    ///
+    /// The default implementation calls `read_str` with a buffer just long
+    /// enough for the `char`, so that nothing after it is consumed, starting
+    /// with 1 byte and growing it to 4 for as long as `read_str` reports an
+    /// [`io::ErrorKind::InvalidInput`] error. Streams which require 4-byte
+    /// buffers may produce several `char`s at once, which is reported as an
+    /// [`io::ErrorKind::Unsupported`] error; they should implement this
+    /// themselves, or be wrapped in a [`Utf8BufReader`].
+    ///
+    /// [`Utf8BufReader`]: crate::Utf8BufReader
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        default_read_char(self)
+    }
+
+    /// Return the next `char` without consuming it, or `None` at the end of
+    /// the stream.
+    ///
+    /// This requires buffering, as the `char` must be read before it can be
+    /// returned. The default implementation reports an
+    /// [`io::ErrorKind::Unsupported`] error; streams without buffering of
+    /// their own can be wrapped in a [`Utf8BufReader`].
+    ///
+    /// [`Utf8BufReader`]: crate::Utf8BufReader
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this stream doesn't support peeking; consider using a Utf8BufReader",
+        ))
+    }
+
+    /// Return an iterator over the `char`s of this stream, using `read_char`.
+    #[inline]
+    fn chars(&mut self) -> Chars<'_, Self>
+    where
+        Self: Sized,
+    {
+        Chars { inner: self }
+    }
+}
+
+/// An iterator over the `char`s of a [`ReadStr`], returned by
+/// [`ReadStr::chars`].
+#[derive(Debug)]
+pub struct Chars<'a, Inner> {
+    inner: &'a mut Inner,
+}
+
+impl<Inner: ReadStr> Iterator for Chars<'_, Inner> {
+    type Item = io::Result<char>;
+
+    #[inline]
+    fn next(&mut self) -> Option<io::Result<char>> {
+        self.inner.read_char().transpose()
+    }
 }
 
 /// Extend the `ReadLayered` trait with `read_str_with_status`, a method for
@@ -26,7 +111,8 @@ pub trait ReadStrLayered: ReadLayered + ReadStr {
     /// check the return value to see how many bytes were written.
     ///
     /// `buf` must be at least 4 bytes long, so that any valid UTF-8 codepoint
-    /// can be read.
+    /// can be read. Implementations which substitute replacement text for
+    /// invalid input may require it to be long enough for the replacement.
     fn read_str_with_status(&mut self, buf: &mut str) -> io::Result<(usize, Status)>;
 
     /// Like `read_exact` but produces the result in a `str`.
@@ -62,6 +148,214 @@ pub fn default_read_exact_str<Inner: ReadStr + ?Sized>(
     }
 }
 
+/// Default implementation of [`ReadStr::read_str_append`].
+pub fn default_read_str_append<Inner: ReadStr + ?Sized>(
+    inner: &mut Inner,
+    buf: &mut String,
+    max: usize,
+) -> io::Result<usize> {
+    // Safety: None of the spare capacity is counted as initialized yet.
+    unsafe { read_str_append_with(inner, buf, max, &mut 0) }
+}
+
+/// Like `default_read_str_append`, but `initialized` is the number of bytes
+/// at the start of the spare capacity of `buf` which hold previous output,
+/// so that a caller reading repeatedly into the same `buf` only zeroes it
+/// once.
+///
+/// # Safety
+///
+/// `initialized` must only count bytes which hold valid UTF-8.
+pub(crate) unsafe fn read_str_append_with<Inner: ReadStr + ?Sized>(
+    inner: &mut Inner,
+    buf: &mut String,
+    max: usize,
+    initialized: &mut usize,
+) -> io::Result<usize> {
+    // Safety: The spare capacity is zeroed before use, and only reused after
+    // `read_str`, which leaves its buffer valid UTF-8. `read_from_str` only
+    // returns lengths at `char` boundaries.
+    read_into_spare_capacity(buf, max, initialized, |bytes| read_from_str(inner, bytes))
+}
+
+/// Default implementation of [`ReadStr::read_str_to_end`].
+pub fn default_read_str_to_end<Inner: ReadStr + ?Sized>(
+    inner: &mut Inner,
+    buf: &mut String,
+) -> io::Result<usize> {
+    // Safety: The spare capacity is zeroed before use, and only reused after
+    // `read_str`, which leaves its buffer valid UTF-8. `read_from_str` only
+    // returns lengths at `char` boundaries.
+    unsafe { read_to_end_with(buf, DEFAULT_BUF_SIZE, |bytes| read_from_str(inner, bytes)) }
+}
+
+/// Default implementation of [`ReadStr::read_char`].
+pub fn default_read_char<Inner: ReadStr + ?Sized>(inner: &mut Inner) -> io::Result<Option<char>> {
+    // Read into a buffer just long enough for the next `char`, so that
+    // nothing after it is consumed. Start with 1 byte, and grow it for as
+    // long as the stream reports that the `char` doesn't fit.
+    let mut len = 1;
+    loop {
+        let mut bytes = [0; 4];
+        let buf = str::from_utf8_mut(&mut bytes[..len]).unwrap();
+        match inner.read_str(buf) {
+            Ok(0) => return Ok(None),
+            Ok(nread) => {
+                let mut chars = buf[..nread].chars();
+                let c = chars.next();
+                if chars.next().is_some() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "read_str produced several chars at once; consider using a Utf8BufReader",
+                    ));
+                }
+                return Ok(c);
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) if err.kind() == io::ErrorKind::InvalidInput && len < 4 => len += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Call `read_str` on `bytes`, and check that the result is at a `char`
+/// boundary.
+///
+/// # Safety
+///
+/// `bytes` must hold valid UTF-8.
+unsafe fn read_from_str<Inner: ReadStr + ?Sized>(
+    inner: &mut Inner,
+    bytes: &mut [u8],
+) -> io::Result<usize> {
+    let buf = str::from_utf8_unchecked_mut(bytes);
+    let nread = inner.read_str(buf)?;
+    check_read_len(buf, nread);
+    Ok(nread)
+}
+
+/// Call `read_str_with_status` on `bytes`, and check that the result is at a
+/// `char` boundary.
+///
+/// # Safety
+///
+/// `bytes` must hold valid UTF-8.
+#[cfg(feature = "layered-io")]
+pub(crate) unsafe fn read_from_str_with_status<Inner: ReadStrLayered + ?Sized>(
+    inner: &mut Inner,
+    bytes: &mut [u8],
+) -> io::Result<(usize, Status)> {
+    let buf = str::from_utf8_unchecked_mut(bytes);
+    let (nread, status) = inner.read_str_with_status(buf)?;
+    check_read_len(buf, nread);
+    Ok((nread, status))
+}
+
+/// Check that `nread`, the length returned by reading into `buf`, is at a
+/// `char` boundary, so that the bytes read can be appended to a `String`.
+pub(crate) fn check_read_len(buf: &str, nread: usize) {
+    assert!(
+        buf.is_char_boundary(nread),
+        "read_str returned a length that isn't at a char boundary"
+    );
+}
+
+/// Call `read` with up to `max` bytes of the spare capacity of `buf`, and
+/// append the bytes it reads. `initialized` is the number of bytes at the
+/// start of the spare capacity which hold previous output from `read`,
+/// which are reused instead of being zeroed again.
+///
+/// # Safety
+///
+/// On success, `read` must have written a valid UTF-8 sequence of the
+/// returned length at the start of its argument.
+pub(crate) unsafe fn read_into_spare_capacity<E>(
+    buf: &mut String,
+    max: usize,
+    initialized: &mut usize,
+    read: impl FnOnce(&mut [u8]) -> Result<usize, E>,
+) -> Result<usize, E> {
+    let vec = buf.as_mut_vec();
+    let len = vec.len();
+
+    // If the buffer is reallocated, its spare capacity isn't preserved.
+    let ptr = vec.as_ptr();
+    vec.reserve(max);
+    if vec.as_ptr() != ptr {
+        *initialized = 0;
+    }
+
+    let spare = &mut vec.spare_capacity_mut()[..max];
+    for byte in spare.iter_mut().skip(*initialized) {
+        byte.write(b'\0');
+    }
+    *initialized = (*initialized).max(max);
+
+    // Safety: We just initialized all of `spare`. `buf` itself is untouched
+    // until we know how much valid UTF-8 was read.
+    let bytes = slice::from_raw_parts_mut(spare.as_mut_ptr().cast::<u8>(), max);
+    let nread = read(bytes)?;
+    assert!(nread <= max);
+    vec.set_len(len + nread);
+    *initialized -= nread;
+    Ok(nread)
+}
+
+/// Like `read_into_spare_capacity`, but for an async `poll_read_str`
+/// function, appending the bytes it reads once it's ready.
+///
+/// # Safety
+///
+/// `initialized` must only count bytes which hold valid UTF-8.
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub(crate) unsafe fn poll_read_into_spare_capacity(
+    buf: &mut String,
+    max: usize,
+    initialized: &mut usize,
+    poll_read_str: impl FnOnce(&mut str) -> Poll<io::Result<usize>>,
+) -> Poll<io::Result<usize>> {
+    let result = read_into_spare_capacity(buf, max, initialized, |bytes| {
+        let buf = str::from_utf8_unchecked_mut(bytes);
+        match poll_read_str(buf) {
+            Poll::Ready(Ok(nread)) => {
+                check_read_len(buf, nread);
+                Ok(nread)
+            }
+            Poll::Ready(Err(err)) => Err(Some(err)),
+            Poll::Pending => Err(None),
+        }
+    });
+    match result {
+        Ok(nread) => Poll::Ready(Ok(nread)),
+        Err(Some(err)) => Poll::Ready(Err(err)),
+        Err(None) => Poll::Pending,
+    }
+}
+
+/// Call `read` repeatedly on the spare capacity of `buf`, `chunk` bytes at a
+/// time, until it reaches the end of the stream.
+///
+/// # Safety
+///
+/// The same as for `read_into_spare_capacity`.
+pub(crate) unsafe fn read_to_end_with(
+    buf: &mut String,
+    chunk: usize,
+    mut read: impl FnMut(&mut [u8]) -> io::Result<usize>,
+) -> io::Result<usize> {
+    let start = buf.len();
+    let mut initialized = 0;
+    loop {
+        let max = chunk.max(buf.capacity() - buf.len());
+        match read_into_spare_capacity(buf, max, &mut initialized, &mut read) {
+            Ok(0) => return Ok(buf.len() - start),
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
 /// Default implementation of [`ReadStrLayered::read_exact_str_using_status`].
 #[cfg(feature = "layered-io")]
 pub fn default_read_exact_str_using_status<Inner: ReadStrLayered + ?Sized>(
@@ -0,0 +1,254 @@
+use crate::{BufReadStr, ReadStr, Utf8Error};
+use std::cmp::min;
+use std::io::{self, BufRead, Cursor, Read};
+
+/// A [`ReadStr`] implementation which reads from a `&str`, handing out
+/// `char`-aligned chunks without re-validating them.
+///
+/// A `read` into a buffer too short for the next scalar value returns as
+/// much of its encoding as fits, and the rest from subsequent `read`s.
+#[derive(Debug, Clone)]
+pub struct StrReader<'a> {
+    /// The text being read.
+    s: &'a str,
+
+    /// The start of the unread part of `s`, which is at a `char` boundary
+    /// unless a `read` has split a scalar value's encoding.
+    pos: usize,
+}
+
+impl<'a> StrReader<'a> {
+    /// Construct a new instance of `StrReader` reading from `s`.
+    #[inline]
+    pub fn new(s: &'a str) -> Self {
+        Self { s, pos: 0 }
+    }
+
+    /// Return the part of the text which hasn't been read yet. If a `read`
+    /// has split a scalar value's encoding, this starts after it.
+    #[inline]
+    pub fn remaining(&self) -> &'a str {
+        let mut pos = self.pos;
+        while !self.s.is_char_boundary(pos) {
+            pos += 1;
+        }
+        &self.s[pos..]
+    }
+
+    /// Return the part of the text which hasn't been read yet, or fail if a
+    /// `read` has split a scalar value's encoding.
+    #[inline]
+    fn remaining_str(&self) -> io::Result<&'a str> {
+        check_not_split(self.s, self.pos)?;
+        Ok(&self.s[self.pos..])
+    }
+
+    /// Return the number of bytes which have been read.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl BufReadStr for StrReader<'_> {
+    #[inline]
+    fn fill_buf_str(&mut self) -> io::Result<&str> {
+        self.remaining_str()
+    }
+}
+
+impl BufRead for StrReader<'_> {
+    #[inline]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(&self.s.as_bytes()[self.pos..])
+    }
+
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        self.pos = min(self.s.len(), self.pos + amt);
+    }
+}
+
+impl ReadStr for StrReader<'_> {
+    #[inline]
+    fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+        let len = copy_str_prefix(self.remaining_str()?, buf)?;
+        self.pos += len;
+        Ok(len)
+    }
+
+    #[inline]
+    fn read_str_append(&mut self, buf: &mut String, max: usize) -> io::Result<usize> {
+        let remaining = self.remaining_str()?;
+        let len = str_prefix_len(remaining, max)?;
+        buf.push_str(&remaining[..len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    #[inline]
+    fn read_str_to_end(&mut self, buf: &mut String) -> io::Result<usize> {
+        let remaining = self.remaining_str()?;
+        buf.push_str(remaining);
+        self.pos = self.s.len();
+        Ok(remaining.len())
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.remaining_str()?.chars().next();
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
+        }
+        Ok(c)
+    }
+
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        Ok(self.remaining_str()?.chars().next())
+    }
+}
+
+impl Read for StrReader<'_> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = read_prefix_len(self.s, self.pos, buf.len());
+        buf[..len].copy_from_slice(&self.s.as_bytes()[self.pos..][..len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    #[inline]
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        self.read_str_to_end(buf)
+    }
+}
+
+impl ReadStr for Cursor<String> {
+    #[inline]
+    fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+        cursor_read_str(self, buf)
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        cursor_read_char(self)
+    }
+
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        Ok(cursor_remaining(self)?.chars().next())
+    }
+}
+
+impl ReadStr for Cursor<&str> {
+    #[inline]
+    fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+        cursor_read_str(self, buf)
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        cursor_read_char(self)
+    }
+
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        Ok(cursor_remaining(self)?.chars().next())
+    }
+}
+
+/// Return the text after the position of a `Cursor` over text.
+fn cursor_remaining<T: AsRef<str>>(cursor: &Cursor<T>) -> io::Result<&str> {
+    let s = cursor.get_ref().as_ref();
+    let pos = min(cursor.position(), s.len() as u64) as usize;
+
+    // `Cursor`'s `Read` implementation can leave it in the middle of a
+    // scalar value's encoding.
+    s.get(pos..).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cursor position isn't at a char boundary",
+        )
+    })
+}
+
+/// Implement `read_str` for a `Cursor` over text.
+fn cursor_read_str<T: AsRef<str>>(cursor: &mut Cursor<T>, buf: &mut str) -> io::Result<usize> {
+    let len = copy_str_prefix(cursor_remaining(cursor)?, buf)?;
+    cursor.set_position(cursor.position() + len as u64);
+    Ok(len)
+}
+
+/// Implement `read_char` for a `Cursor` over text.
+fn cursor_read_char<T: AsRef<str>>(cursor: &mut Cursor<T>) -> io::Result<Option<char>> {
+    let c = cursor_remaining(cursor)?.chars().next();
+    if let Some(c) = c {
+        cursor.set_position(cursor.position() + c.len_utf8() as u64);
+    }
+    Ok(c)
+}
+
+/// Return the length of the longest prefix of `available` which is at most
+/// `max` bytes long and doesn't split a scalar value's encoding.
+pub(crate) fn str_prefix_len(available: &str, max: usize) -> io::Result<usize> {
+    let mut len = min(available.len(), max);
+    while !available.is_char_boundary(len) {
+        len -= 1;
+    }
+    if len == 0 && !available.is_empty() && max != 0 {
+        return Err(Utf8Error::BufferTooSmall {
+            len: max,
+            min_len: available.chars().next().unwrap().len_utf8(),
+        }
+        .into());
+    }
+    Ok(len)
+}
+
+/// Return the length of the longest prefix of `s[pos..]` which is at most
+/// `max` bytes long and ends at a `char` boundary, or if there isn't one, as
+/// much of the rest of the next scalar value's encoding as fits. `pos` may be
+/// partway through a scalar value's encoding.
+pub(crate) fn read_prefix_len(s: &str, pos: usize, max: usize) -> usize {
+    let len = min(s.len() - pos, max);
+    let mut end = pos + len;
+    while end > pos && !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end == pos {
+        len
+    } else {
+        end - pos
+    }
+}
+
+/// Fail if `pos` is partway through a scalar value's encoding in `s`, after
+/// a `read` split it, as the rest of it can't be returned in a `str`.
+pub(crate) fn check_not_split(s: &str, pos: usize) -> io::Result<()> {
+    if !s.is_char_boundary(pos) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a previous read split a scalar value's encoding; read the rest of it with `read`",
+        ));
+    }
+    Ok(())
+}
+
+/// Copy as much of `available` into `buf` as fits without splitting a
+/// scalar value's encoding, and return the number of bytes copied.
+pub(crate) fn copy_str_prefix(available: &str, buf: &mut str) -> io::Result<usize> {
+    let len = str_prefix_len(available, buf.len())?;
+
+    // Safety: We copy complete encodings, and overwrite any partial encoding
+    // left after them.
+    let bytes = unsafe { buf.as_bytes_mut() };
+    bytes[..len].copy_from_slice(&available.as_bytes()[..len]);
+    for byte in bytes[len..]
+        .iter_mut()
+        .take_while(|byte| **byte & 0xc0 == 0x80)
+    {
+        *byte = b'\0';
+    }
+    Ok(len)
+}
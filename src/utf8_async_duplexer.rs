@@ -0,0 +1,261 @@
+#[cfg(feature = "futures-io")]
+use crate::futures_io::{
+    AsyncReadStr as FuturesAsyncReadStr, AsyncWriteStr as FuturesAsyncWriteStr,
+};
+#[cfg(feature = "tokio")]
+use crate::tokio::{
+    poll_read_bytes, AsyncReadStr as TokioAsyncReadStr, AsyncWriteStr as TokioAsyncWriteStr,
+};
+use crate::utf8_async_output::Utf8AsyncOutput;
+use crate::utf8_async_reader::poll_read_str;
+use crate::utf8_input::Utf8Input;
+use crate::{Granularity, InvalidPolicy, InvalidSequence, Replacement};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::{fmt, io};
+
+/// An interactive async UTF-8 stream, combining `Utf8AsyncReader` and
+/// `Utf8AsyncWriter`.
+pub struct Utf8AsyncDuplexer<Inner> {
+    /// The wrapped byte stream.
+    inner: Inner,
+
+    /// UTF-8 translation state.
+    input: Utf8Input,
+    output: Utf8AsyncOutput,
+}
+
+impl<Inner> Utf8AsyncDuplexer<Inner> {
+    /// Construct a new instance of `Utf8AsyncDuplexer` wrapping `inner`.
+    #[inline]
+    pub fn new(inner: Inner) -> Self {
+        Self::with_policy(inner, InvalidPolicy::Replace)
+    }
+
+    /// Construct a new instance of `Utf8AsyncDuplexer` wrapping `inner`,
+    /// which handles invalid input sequences according to `policy`.
+    #[inline]
+    pub fn with_policy(inner: Inner, policy: InvalidPolicy) -> Self {
+        Self {
+            inner,
+            input: Utf8Input::new(policy),
+            output: Utf8AsyncOutput::new(),
+        }
+    }
+
+    /// Set how many replacements are produced for each invalid sequence.
+    /// The default is [`Granularity::MaximalSubpart`].
+    #[inline]
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.input.decoder.set_granularity(granularity);
+        self
+    }
+
+    /// Set the text which invalid sequences are replaced with when using
+    /// [`InvalidPolicy::Replace`]. The default is U+FFFD.
+    #[inline]
+    pub fn replacement(mut self, replacement: impl Into<Replacement>) -> Self {
+        self.input.decoder.set_replacement(replacement.into());
+        self
+    }
+
+    /// Register a callback which is called with each invalid sequence
+    /// encountered in the input, before it is handled according to the
+    /// duplexer's [`InvalidPolicy`].
+    #[inline]
+    pub fn on_invalid(
+        mut self,
+        on_invalid: impl FnMut(&InvalidSequence) + Send + Sync + 'static,
+    ) -> Self {
+        self.input.decoder.set_on_invalid(on_invalid);
+        self
+    }
+
+    /// Return a reference to the inner stream.
+    #[inline]
+    pub fn get_ref(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Return a mutable reference to the inner stream.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    /// Return the inner stream, discarding any buffered input and pending
+    /// output. Flush the duplexer first to ensure that no output is lost.
+    #[inline]
+    pub fn into_inner(self) -> Inner {
+        self.inner
+    }
+
+    /// Project a pinned reference to this duplexer into its fields.
+    #[inline]
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut Inner>, &mut Utf8Input, &mut Utf8AsyncOutput) {
+        // Safety: `inner` is structurally pinned, and is never moved out of
+        // a pinned `Utf8AsyncDuplexer`. The other fields are `Unpin`.
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (
+                Pin::new_unchecked(&mut this.inner),
+                &mut this.input,
+                &mut this.output,
+            )
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncRead> ::tokio::io::AsyncRead for Utf8AsyncDuplexer<Inner> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ::tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let (mut inner, input, _) = self.project();
+        let size = ready!(input.poll_read(buf.initialize_unfilled(), |bytes| {
+            poll_read_bytes(inner.as_mut(), cx, bytes)
+        }))?;
+        buf.advance(size);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncRead> TokioAsyncReadStr for Utf8AsyncDuplexer<Inner> {
+    #[inline]
+    fn poll_read_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, input, _) = self.project();
+        // Safety: `Utf8Input::poll_read` only produces complete UTF-8
+        // encodings.
+        unsafe {
+            poll_read_str(buf, |bytes| {
+                input.poll_read(bytes, |bytes| poll_read_bytes(inner.as_mut(), cx, bytes))
+            })
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncWrite> ::tokio::io::AsyncWrite for Utf8AsyncDuplexer<Inner> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, _, output) = self.project();
+        output.poll_write(buf, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, _, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, _, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncWrite> TokioAsyncWriteStr for Utf8AsyncDuplexer<Inner> {
+    #[inline]
+    fn poll_write_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        s: &str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, _, output) = self.project();
+        output.poll_write_str(s, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncRead> ::futures_io::AsyncRead for Utf8AsyncDuplexer<Inner> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, input, _) = self.project();
+        input.poll_read(buf, |bytes| inner.as_mut().poll_read(cx, bytes))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncRead> FuturesAsyncReadStr for Utf8AsyncDuplexer<Inner> {
+    #[inline]
+    fn poll_read_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, input, _) = self.project();
+        // Safety: `Utf8Input::poll_read` only produces complete UTF-8
+        // encodings.
+        unsafe {
+            poll_read_str(buf, |bytes| {
+                input.poll_read(bytes, |bytes| inner.as_mut().poll_read(cx, bytes))
+            })
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncWrite> ::futures_io::AsyncWrite for Utf8AsyncDuplexer<Inner> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, _, output) = self.project();
+        output.poll_write(buf, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, _, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, _, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_close(cx)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncWrite> FuturesAsyncWriteStr for Utf8AsyncDuplexer<Inner> {
+    #[inline]
+    fn poll_write_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        s: &str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, _, output) = self.project();
+        output.poll_write_str(s, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+}
+
+impl<Inner: fmt::Debug> fmt::Debug for Utf8AsyncDuplexer<Inner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("Utf8AsyncDuplexer");
+        b.field("inner", &self.inner);
+        b.finish()
+    }
+}
@@ -0,0 +1,195 @@
+use crate::invalid::invalid_output;
+use crate::{Utf8Error, Utf8Validator};
+use std::io;
+use std::str;
+use std::task::{ready, Poll};
+
+/// UTF-8 translation state for the async writers.
+///
+/// All state which spans calls lives here rather than in any future, so
+/// dropping a future between polls loses nothing.
+pub(crate) struct Utf8AsyncOutput {
+    /// Validation state for bytes written with `poll_write`, holding the
+    /// start of a scalar value's encoding which hasn't been completed yet.
+    validator: Utf8Validator,
+
+    /// The rest of a scalar value's encoding which has been accepted, but
+    /// which the inner stream hasn't accepted yet.
+    pending: [u8; 4],
+    pending_pos: u8,
+    pending_len: u8,
+
+    /// The number of bytes accepted so far, not counting any incomplete
+    /// encoding held by `validator`, which is the offset of any incomplete or
+    /// invalid encoding which follows.
+    written: u64,
+}
+
+impl Utf8AsyncOutput {
+    /// Construct a new instance of `Utf8AsyncOutput`.
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self {
+            validator: Utf8Validator::new(),
+            pending: [0; 4],
+            pending_pos: 0,
+            pending_len: 0,
+            written: 0,
+        }
+    }
+
+    /// Write `pending` to the inner stream, using `poll_inner`.
+    fn poll_pending(
+        &mut self,
+        poll_inner: &mut impl FnMut(&[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<()>> {
+        while self.pending_pos < self.pending_len {
+            let pending =
+                &self.pending[usize::from(self.pending_pos)..usize::from(self.pending_len)];
+            match ready!(poll_inner(pending))? {
+                0 => {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole scalar value",
+                    )))
+                }
+                n => self.pending_pos += n as u8,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Write a prefix of `s` to the inner stream, using `poll_inner`, and
+    /// return the number of bytes of `s` written, which is always at a
+    /// `char` boundary.
+    pub(crate) fn poll_write_str(
+        &mut self,
+        s: &str,
+        mut poll_inner: impl FnMut(&[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        if !self.validator.incomplete().is_empty() {
+            return Poll::Ready(Err(self.write_str_error()));
+        }
+        ready!(self.poll_pending(&mut poll_inner))?;
+        self.poll_write_valid(s, &mut poll_inner)
+    }
+
+    /// Write a prefix of `buf` to the inner stream, using `poll_inner`, and
+    /// return the number of bytes of `buf` accepted. Bytes which may be the
+    /// start of a scalar value's encoding are held until they're completed.
+    pub(crate) fn poll_write(
+        &mut self,
+        buf: &[u8],
+        mut poll_inner: impl FnMut(&[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_pending(&mut poll_inner))?;
+
+        // If we have incomplete bytes from the previous `poll_write`, try to
+        // complete them.
+        if !self.validator.incomplete().is_empty() {
+            let (copy_len, completed) = self.validator.complete(buf);
+
+            // If the sequence is still incomplete, wait for the next write.
+            let (incomplete, utf8_len) = match completed {
+                Some(completed) => completed,
+                None => return Poll::Ready(Ok(copy_len)),
+            };
+
+            // The sequence is complete; queue it up to be written. The bytes
+            // are accepted even if the inner stream isn't ready for them.
+            if let Err(error) = str::from_utf8(&incomplete[..utf8_len]) {
+                let invalid = &incomplete[..error.error_len().unwrap()];
+                return Poll::Ready(Err(invalid_output(self.written, invalid)));
+            }
+            self.pending = incomplete;
+            self.pending_pos = 0;
+            self.pending_len = utf8_len as u8;
+            self.written += utf8_len as u64;
+            if let Poll::Ready(Err(err)) = self.poll_pending(&mut poll_inner) {
+                return Poll::Ready(Err(err));
+            }
+            return Poll::Ready(Ok(copy_len));
+        }
+
+        // Write the valid prefix of `buf`. If `buf` starts with incomplete
+        // bytes, save them for the next `poll_write`. If it starts with
+        // invalid bytes, fail.
+        let error = match str::from_utf8(buf) {
+            Ok(s) => return self.poll_write_valid(s, &mut poll_inner),
+            Err(error) => error,
+        };
+        let valid_up_to = error.valid_up_to();
+        if valid_up_to != 0 {
+            // Safety: `from_utf8` validated this part of `buf`.
+            let valid = unsafe { str::from_utf8_unchecked(&buf[..valid_up_to]) };
+            self.poll_write_valid(valid, &mut poll_inner)
+        } else if error.error_len().is_none() {
+            self.validator.hold(buf);
+            Poll::Ready(Ok(buf.len()))
+        } else {
+            let invalid = &buf[..error.error_len().unwrap()];
+            Poll::Ready(Err(invalid_output(self.written, invalid)))
+        }
+    }
+
+    /// Write a prefix of `s`. If the inner stream accepts part of a scalar
+    /// value's encoding, hold on to the rest of it in `pending`, so that
+    /// the returned length is at a `char` boundary.
+    fn poll_write_valid(
+        &mut self,
+        s: &str,
+        poll_inner: &mut impl FnMut(&[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        if s.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let n = ready!(poll_inner(s.as_bytes()))?;
+        let mut end = n;
+        while !s.is_char_boundary(end) {
+            end += 1;
+        }
+        if end != n {
+            self.pending[..end - n].copy_from_slice(&s.as_bytes()[n..end]);
+            self.pending_pos = 0;
+            self.pending_len = (end - n) as u8;
+        }
+        self.written += end as u64;
+        Poll::Ready(Ok(end))
+    }
+
+    /// Write any pending bytes and check that no incomplete encoding is
+    /// pending, so that the inner stream can be flushed or closed.
+    pub(crate) fn poll_finish(
+        &mut self,
+        mut poll_inner: impl FnMut(&[u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.poll_pending(&mut poll_inner))?;
+        if !self.validator.incomplete().is_empty() {
+            let error = self.incomplete_error();
+            self.validator.take_incomplete();
+            return Poll::Ready(Err(error));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Return the error for an incomplete encoding which is still pending.
+    #[cold]
+    fn incomplete_error(&self) -> io::Error {
+        Utf8Error::IncompleteAtFlush {
+            offset: self.written,
+            bytes: self.validator.incomplete().to_vec(),
+        }
+        .into()
+    }
+
+    /// Return the error for a `write_str` while an incomplete encoding is
+    /// pending.
+    #[cold]
+    fn write_str_error(&self) -> io::Error {
+        Utf8Error::IncompleteAtWriteStr {
+            offset: self.written,
+            bytes: self.validator.incomplete().to_vec(),
+        }
+        .into()
+    }
+}
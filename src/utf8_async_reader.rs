@@ -0,0 +1,240 @@
+#[cfg(feature = "futures-io")]
+use crate::futures_io::AsyncReadStr as FuturesAsyncReadStr;
+#[cfg(feature = "tokio")]
+use crate::tokio::{poll_read_bytes, AsyncReadStr as TokioAsyncReadStr};
+use crate::utf8_input::Utf8Input;
+use crate::{Encoding, Granularity, InvalidPolicy, InvalidSequence, Replacement};
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::ready;
+use std::task::{Context, Poll};
+use std::{fmt, io};
+
+/// An async counterpart to [`Utf8Reader`], which translates from an input
+/// `AsyncRead` producing an arbitrary byte sequence into a valid UTF-8
+/// sequence, where scalar value encodings never straddle reads.
+///
+/// This implements the `AsyncRead` traits of [`tokio`] and [`futures-io`],
+/// when the respective cargo features are enabled. Buffers must be at least
+/// 4 bytes long, and long enough for the replacement.
+///
+/// All translation state is held in the reader, so a read future may be
+/// dropped at any point without losing input.
+///
+/// [`Utf8Reader`]: crate::Utf8Reader
+/// [`tokio`]: https://docs.rs/tokio
+/// [`futures-io`]: https://docs.rs/futures-io
+pub struct Utf8AsyncReader<Inner> {
+    /// The wrapped byte stream.
+    inner: Inner,
+
+    /// UTF-8 translation state.
+    input: Utf8Input,
+}
+
+impl<Inner> Utf8AsyncReader<Inner> {
+    /// Construct a new instance of `Utf8AsyncReader` wrapping `inner`.
+    #[inline]
+    pub fn new(inner: Inner) -> Self {
+        Self::with_policy(inner, InvalidPolicy::Replace)
+    }
+
+    /// Construct a new instance of `Utf8AsyncReader` wrapping `inner`,
+    /// which handles invalid sequences according to `policy`.
+    #[inline]
+    pub fn with_policy(inner: Inner, policy: InvalidPolicy) -> Self {
+        Self {
+            inner,
+            input: Utf8Input::new(policy),
+        }
+    }
+
+    /// Set how many replacements are produced for each invalid sequence.
+    /// The default is [`Granularity::MaximalSubpart`].
+    #[inline]
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.input.decoder.set_granularity(granularity);
+        self
+    }
+
+    /// Set the text which invalid sequences are replaced with when using
+    /// [`InvalidPolicy::Replace`]. The default is U+FFFD.
+    #[inline]
+    pub fn replacement(mut self, replacement: impl Into<Replacement>) -> Self {
+        self.input.decoder.set_replacement(replacement.into());
+        self
+    }
+
+    /// Set whether to strip a leading UTF-8 byte order mark (U+FEFF) from
+    /// the input. By default it's passed through.
+    #[inline]
+    pub fn strip_bom(mut self, strip_bom: bool) -> Self {
+        self.input.decoder.set_strip_bom(strip_bom);
+        self
+    }
+
+    /// When stripping a byte order mark or detecting the encoding, return
+    /// whether a byte order mark was present, or `None` if not enough input
+    /// has been read to tell yet, or if neither is enabled.
+    #[inline]
+    pub fn had_bom(&self) -> Option<bool> {
+        self.input.decoder.had_bom()
+    }
+
+    /// Set whether to detect the encoding of the input from a leading byte
+    /// order mark. See [`Utf8Reader::detect_encoding`].
+    ///
+    /// [`Utf8Reader::detect_encoding`]: crate::Utf8Reader::detect_encoding
+    #[inline]
+    pub fn detect_encoding(mut self, detect_encoding: bool) -> Self {
+        self.input.decoder.set_detect_encoding(detect_encoding);
+        self
+    }
+
+    /// Return the encoding of the input, or `None` if the encoding is being
+    /// detected and not enough input has been read to tell yet.
+    #[inline]
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.input.decoder.encoding()
+    }
+
+    /// Set whether to accept CESU-8 surrogate pairs and Modified UTF-8's
+    /// encoding of U+0000. See [`Utf8Reader::modified_utf8`].
+    ///
+    /// [`Utf8Reader::modified_utf8`]: crate::Utf8Reader::modified_utf8
+    #[inline]
+    pub fn modified_utf8(mut self, modified_utf8: bool) -> Self {
+        self.input.decoder.set_modified_utf8(modified_utf8);
+        self
+    }
+
+    /// Register a callback which is called with each invalid sequence
+    /// encountered in the input, before it is handled according to the
+    /// reader's [`InvalidPolicy`].
+    #[inline]
+    pub fn on_invalid(
+        mut self,
+        on_invalid: impl FnMut(&InvalidSequence) + Send + Sync + 'static,
+    ) -> Self {
+        self.input.decoder.set_on_invalid(on_invalid);
+        self
+    }
+
+    /// Return a reference to the inner stream.
+    #[inline]
+    pub fn get_ref(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Return a mutable reference to the inner stream. Reading from it
+    /// directly may skip input which has been buffered.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    /// Project a pinned reference to this reader into its fields.
+    #[inline]
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut Inner>, &mut Utf8Input) {
+        // Safety: `inner` is structurally pinned, and is never moved out of
+        // a pinned `Utf8AsyncReader`. `Utf8Input` is `Unpin`.
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.inner), &mut this.input)
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncRead> ::tokio::io::AsyncRead for Utf8AsyncReader<Inner> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ::tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let (mut inner, input) = self.project();
+        let size = ready!(input.poll_read(buf.initialize_unfilled(), |bytes| {
+            poll_read_bytes(inner.as_mut(), cx, bytes)
+        }))?;
+        buf.advance(size);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncRead> TokioAsyncReadStr for Utf8AsyncReader<Inner> {
+    #[inline]
+    fn poll_read_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, input) = self.project();
+        // Safety: `Utf8Input::poll_read` only produces complete UTF-8
+        // encodings.
+        unsafe {
+            poll_read_str(buf, |bytes| {
+                input.poll_read(bytes, |bytes| poll_read_bytes(inner.as_mut(), cx, bytes))
+            })
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncRead> ::futures_io::AsyncRead for Utf8AsyncReader<Inner> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, input) = self.project();
+        input.poll_read(buf, |bytes| inner.as_mut().poll_read(cx, bytes))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncRead> FuturesAsyncReadStr for Utf8AsyncReader<Inner> {
+    #[inline]
+    fn poll_read_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, input) = self.project();
+        // Safety: `Utf8Input::poll_read` only produces complete UTF-8
+        // encodings.
+        unsafe {
+            poll_read_str(buf, |bytes| {
+                input.poll_read(bytes, |bytes| inner.as_mut().poll_read(cx, bytes))
+            })
+        }
+    }
+}
+
+/// Read into `buf` using `read`, which reads into bytes, and overwrite the
+/// rest of `buf`, which may hold raw input.
+///
+/// # Safety
+///
+/// `read` must only produce complete UTF-8 encodings.
+pub(crate) unsafe fn poll_read_str(
+    buf: &mut str,
+    read: impl FnOnce(&mut [u8]) -> Poll<io::Result<usize>>,
+) -> Poll<io::Result<usize>> {
+    let bytes = buf.as_bytes_mut();
+    let result = read(bytes);
+    let size = match &result {
+        Poll::Ready(Ok(size)) => *size,
+        _ => 0,
+    };
+    bytes[size..].fill(b'\0');
+    result
+}
+
+impl<Inner: fmt::Debug> fmt::Debug for Utf8AsyncReader<Inner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("Utf8AsyncReader");
+        b.field("inner", &self.inner);
+        b.finish()
+    }
+}
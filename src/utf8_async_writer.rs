@@ -0,0 +1,161 @@
+#[cfg(feature = "futures-io")]
+use crate::futures_io::AsyncWriteStr as FuturesAsyncWriteStr;
+#[cfg(feature = "tokio")]
+use crate::tokio::AsyncWriteStr as TokioAsyncWriteStr;
+use crate::utf8_async_output::Utf8AsyncOutput;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::{fmt, io};
+
+/// An async counterpart to [`Utf8Writer`], which translates into an output
+/// `AsyncWrite` producing a valid UTF-8 sequence from an arbitrary byte
+/// sequence. Attempts to write invalid encodings are reported as errors.
+///
+/// This implements the `AsyncWrite` traits of [`tokio`] and [`futures-io`],
+/// when the respective cargo features are enabled.
+///
+/// All translation state is held in the writer, so a write future may be
+/// dropped at any point without splitting a scalar value's encoding. Unlike
+/// `Utf8Writer`, dropping a `Utf8AsyncWriter` with an incomplete encoding
+/// pending doesn't panic; flush or close it to check for one.
+///
+/// [`Utf8Writer`]: crate::Utf8Writer
+/// [`tokio`]: https://docs.rs/tokio
+/// [`futures-io`]: https://docs.rs/futures-io
+pub struct Utf8AsyncWriter<Inner> {
+    /// The wrapped byte stream.
+    inner: Inner,
+
+    /// UTF-8 translation state.
+    output: Utf8AsyncOutput,
+}
+
+impl<Inner> Utf8AsyncWriter<Inner> {
+    /// Construct a new instance of `Utf8AsyncWriter` wrapping `inner`.
+    #[inline]
+    pub fn new(inner: Inner) -> Self {
+        Self {
+            inner,
+            output: Utf8AsyncOutput::new(),
+        }
+    }
+
+    /// Return a reference to the inner stream.
+    #[inline]
+    pub fn get_ref(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Return a mutable reference to the inner stream. Writing to it
+    /// directly may split a scalar value's encoding.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    /// Return the inner stream, discarding any pending output. Flush the
+    /// writer first to ensure that nothing is lost.
+    #[inline]
+    pub fn into_inner(self) -> Inner {
+        self.inner
+    }
+
+    /// Project a pinned reference to this writer into its fields.
+    #[inline]
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut Inner>, &mut Utf8AsyncOutput) {
+        // Safety: `inner` is structurally pinned, and is never moved out of
+        // a pinned `Utf8AsyncWriter`. `Utf8AsyncOutput` is `Unpin`.
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.inner), &mut this.output)
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncWrite> ::tokio::io::AsyncWrite for Utf8AsyncWriter<Inner> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, output) = self.project();
+        output.poll_write(buf, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Inner: ::tokio::io::AsyncWrite> TokioAsyncWriteStr for Utf8AsyncWriter<Inner> {
+    #[inline]
+    fn poll_write_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        s: &str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, output) = self.project();
+        output.poll_write_str(s, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncWrite> ::futures_io::AsyncWrite for Utf8AsyncWriter<Inner> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, output) = self.project();
+        output.poll_write(buf, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (mut inner, output) = self.project();
+        ready!(output.poll_finish(|bytes| inner.as_mut().poll_write(cx, bytes)))?;
+        inner.poll_close(cx)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<Inner: ::futures_io::AsyncWrite> FuturesAsyncWriteStr for Utf8AsyncWriter<Inner> {
+    #[inline]
+    fn poll_write_str(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        s: &str,
+    ) -> Poll<io::Result<usize>> {
+        let (mut inner, output) = self.project();
+        output.poll_write_str(s, |bytes| inner.as_mut().poll_write(cx, bytes))
+    }
+}
+
+impl<Inner: fmt::Debug> fmt::Debug for Utf8AsyncWriter<Inner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("Utf8AsyncWriter");
+        b.field("inner", &self.inner);
+        b.finish()
+    }
+}
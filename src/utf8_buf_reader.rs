@@ -0,0 +1,198 @@
+use crate::str_reader::{check_not_split, copy_str_prefix, read_prefix_len, str_prefix_len};
+use crate::{BufReadStr, ReadStr, DEFAULT_BUF_SIZE};
+use std::io::{self, BufRead, Read};
+use std::{fmt, str};
+
+/// A buffering wrapper around a [`ReadStr`] implementation, like
+/// [`std::io::BufReader`], which implements [`BufReadStr`], so that buffered
+/// data can be accessed as a `str` without re-validating it.
+///
+/// Like other UTF-8 streams, `read_str` never splits a scalar value's
+/// encoding, while a `read` into a buffer too short for the next scalar
+/// value returns as much of its encoding as fits, and the rest from
+/// subsequent `read`s.
+pub struct Utf8BufReader<Inner: ReadStr> {
+    /// The wrapped stream.
+    inner: Inner,
+
+    /// The buffer, which always holds valid UTF-8.
+    buf: Box<str>,
+
+    /// The start of the unconsumed data in `buf`, which is at a `char`
+    /// boundary unless a `read` has split a scalar value's encoding.
+    pos: usize,
+
+    /// The end of the data in `buf`.
+    filled: usize,
+}
+
+impl<Inner: ReadStr> Utf8BufReader<Inner> {
+    /// Construct a new instance of `Utf8BufReader` wrapping `inner`, with a
+    /// default buffer capacity.
+    #[inline]
+    pub fn new(inner: Inner) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    /// Construct a new instance of `Utf8BufReader` wrapping `inner`, with a
+    /// buffer of `capacity` bytes, which must be at least as long as `inner`
+    /// requires for `read_str`.
+    #[inline]
+    pub fn with_capacity(capacity: usize, inner: Inner) -> Self {
+        Self {
+            inner,
+            buf: "\0".repeat(capacity).into_boxed_str(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// Return a reference to the wrapped stream.
+    #[inline]
+    pub fn get_ref(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Return a mutable reference to the wrapped stream. Reading from it
+    /// directly skips any buffered data.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    /// Return the buffered data which hasn't been consumed yet. If a `read`
+    /// has split a scalar value's encoding, this starts after it.
+    #[inline]
+    pub fn buffer_str(&self) -> &str {
+        let mut pos = self.pos;
+        while !self.buf.is_char_boundary(pos) {
+            pos += 1;
+        }
+        &self.buf[pos..self.filled]
+    }
+
+    /// If all of the buffered data has been consumed, read more.
+    #[inline]
+    fn fill(&mut self) -> io::Result<()> {
+        if self.pos >= self.filled {
+            self.filled = self.inner.read_str(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(())
+    }
+
+    /// Unwrap this `Utf8BufReader`, returning the wrapped stream. Any
+    /// buffered data is lost.
+    #[inline]
+    pub fn into_inner(self) -> Inner {
+        self.inner
+    }
+}
+
+impl<Inner: ReadStr> BufReadStr for Utf8BufReader<Inner> {
+    fn fill_buf_str(&mut self) -> io::Result<&str> {
+        self.fill()?;
+        check_not_split(&self.buf, self.pos)?;
+        Ok(&self.buf[self.pos..self.filled])
+    }
+}
+
+impl<Inner: ReadStr> BufRead for Utf8BufReader<Inner> {
+    #[inline]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.fill()?;
+        Ok(&self.buf.as_bytes()[self.pos..self.filled])
+    }
+
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        self.pos = self.filled.min(self.pos + amt);
+    }
+}
+
+impl<Inner: ReadStr> ReadStr for Utf8BufReader<Inner> {
+    fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+        // If we don't have any buffered data and we're reading at least as
+        // much as we'd buffer, bypass the buffer.
+        if self.pos >= self.filled && buf.len() >= self.buf.len() {
+            return self.inner.read_str(buf);
+        }
+
+        let len = copy_str_prefix(self.fill_buf_str()?, buf)?;
+        self.consume(len);
+        Ok(len)
+    }
+
+    fn read_str_append(&mut self, buf: &mut String, max: usize) -> io::Result<usize> {
+        let available = self.fill_buf_str()?;
+
+        // Don't split a scalar value's encoding.
+        let len = str_prefix_len(available, max)?;
+
+        buf.push_str(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+
+    fn read_str_to_end(&mut self, buf: &mut String) -> io::Result<usize> {
+        let mut read = 0;
+        loop {
+            let available = match self.fill_buf_str() {
+                Ok("") => return Ok(read),
+                Ok(available) => available,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            buf.push_str(available);
+            let len = available.len();
+            self.consume(len);
+            read += len;
+        }
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.peek_char()?;
+        if let Some(c) = c {
+            self.consume(c.len_utf8());
+        }
+        Ok(c)
+    }
+
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        loop {
+            match self.fill_buf_str() {
+                Ok(available) => return Ok(available.chars().next()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<Inner: ReadStr> Read for Utf8BufReader<Inner> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill()?;
+        let len = read_prefix_len(&self.buf[..self.filled], self.pos, buf.len());
+        buf[..len].copy_from_slice(&self.buf.as_bytes()[self.pos..][..len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    #[inline]
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        self.read_str_to_end(buf)
+    }
+}
+
+impl<Inner: ReadStr + fmt::Debug> fmt::Debug for Utf8BufReader<Inner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("Utf8BufReader");
+        b.field("inner", &self.inner);
+        b.field(
+            "buffer",
+            &format_args!("{}/{}", self.filled - self.pos, self.buf.len()),
+        );
+        b.finish()
+    }
+}
@@ -0,0 +1,679 @@
+use crate::encoding::{decode_modified_utf8, Decoded};
+use crate::invalid::find_escape;
+use crate::{Encoding, Granularity, InvalidPolicy, InvalidSequence, Replacement};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cmp::max;
+use core::cmp::min;
+use core::{fmt, str};
+
+/// A callback to report invalid sequences to.
+type OnInvalid = Box<dyn FnMut(&InvalidSequence) + Send + Sync>;
+
+/// The most input which is ever held between calls: all but the last byte
+/// of a CESU-8 surrogate pair.
+const MAX_PENDING: usize = 5;
+
+/// The longest sequence which is decoded as a unit: a CESU-8 surrogate pair.
+const MAX_UNIT: usize = 6;
+
+/// An incremental decoder which translates an arbitrary byte sequence,
+/// pushed to it a piece at a time, into valid UTF-8, in the same way as
+/// [`Utf8Reader`]. Scalar values split across pieces are held until they're
+/// completed.
+///
+/// This doesn't perform any I/O itself, so it can be used with input from
+/// any source, such as messages from a channel or frames from a network
+/// protocol. Between calls, it holds at most a few bytes of input, in a
+/// fixed-size buffer.
+///
+/// [`Utf8Reader`]: crate::Utf8Reader
+pub struct Utf8Decoder {
+    /// The start of a sequence, or of a byte order mark, which has been
+    /// consumed but which can't be translated until more input arrives.
+    pending: [u8; MAX_PENDING],
+    pending_len: u8,
+
+    /// What to do with invalid sequences.
+    policy: InvalidPolicy,
+
+    /// How finely to divide invalid sequences.
+    granularity: Granularity,
+
+    /// The text to replace invalid sequences with.
+    replacement: Replacement,
+
+    /// A callback to report invalid sequences to.
+    on_invalid: Option<OnInvalid>,
+
+    /// The total number of bytes of input consumed, including `pending`.
+    read_total: u64,
+
+    /// Byte order mark handling.
+    bom: BomState,
+
+    /// Whether to strip a leading UTF-8 byte order mark.
+    strip_bom: bool,
+
+    /// Whether to detect the encoding from a leading byte order mark.
+    detect_encoding: bool,
+
+    /// The encoding of the input.
+    encoding: Encoding,
+
+    /// Whether to accept CESU-8 surrogate pairs and Modified UTF-8's
+    /// encoding of U+0000.
+    modified_utf8: bool,
+}
+
+impl Utf8Decoder {
+    /// Construct a new instance of `Utf8Decoder`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_policy(InvalidPolicy::Replace)
+    }
+
+    /// Construct a new instance of `Utf8Decoder`, which handles invalid
+    /// sequences according to `policy`.
+    #[inline]
+    pub const fn with_policy(policy: InvalidPolicy) -> Self {
+        Self {
+            pending: [0; MAX_PENDING],
+            pending_len: 0,
+            policy,
+            granularity: Granularity::MaximalSubpart,
+            replacement: Replacement::REPLACEMENT_CHARACTER,
+            on_invalid: None,
+            read_total: 0,
+            bom: BomState::Keep,
+            strip_bom: false,
+            detect_encoding: false,
+            encoding: Encoding::Utf8,
+            modified_utf8: false,
+        }
+    }
+
+    /// Set how many replacements are produced for each invalid sequence.
+    /// The default is [`Granularity::MaximalSubpart`].
+    #[inline]
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.set_granularity(granularity);
+        self
+    }
+
+    /// Set the text which invalid sequences are replaced with when using
+    /// [`InvalidPolicy::Replace`]. The default is U+FFFD.
+    #[inline]
+    pub fn replacement(mut self, replacement: impl Into<Replacement>) -> Self {
+        self.set_replacement(replacement.into());
+        self
+    }
+
+    /// Set whether to strip a leading UTF-8 byte order mark (U+FEFF) from
+    /// the input. By default it's passed through.
+    #[inline]
+    pub fn strip_bom(mut self, strip_bom: bool) -> Self {
+        self.set_strip_bom(strip_bom);
+        self
+    }
+
+    /// Set whether to detect the encoding of the input from a leading byte
+    /// order mark. See [`Utf8Reader::detect_encoding`].
+    ///
+    /// [`Utf8Reader::detect_encoding`]: crate::Utf8Reader::detect_encoding
+    #[inline]
+    pub fn detect_encoding(mut self, detect_encoding: bool) -> Self {
+        self.set_detect_encoding(detect_encoding);
+        self
+    }
+
+    /// Set whether to accept CESU-8 surrogate pairs and Modified UTF-8's
+    /// encoding of U+0000. See [`Utf8Reader::modified_utf8`].
+    ///
+    /// [`Utf8Reader::modified_utf8`]: crate::Utf8Reader::modified_utf8
+    #[inline]
+    pub fn modified_utf8(mut self, modified_utf8: bool) -> Self {
+        self.set_modified_utf8(modified_utf8);
+        self
+    }
+
+    /// Register a callback which is called with each invalid sequence
+    /// encountered in the input, before it is handled according to the
+    /// decoder's [`InvalidPolicy`].
+    #[inline]
+    pub fn on_invalid(
+        mut self,
+        on_invalid: impl FnMut(&InvalidSequence) + Send + Sync + 'static,
+    ) -> Self {
+        self.set_on_invalid(on_invalid);
+        self
+    }
+
+    /// Decode `input`, the next piece of the input stream, appending the
+    /// output to `out`, and advance `input` past the bytes consumed. Set
+    /// `last` on the final piece, so that an incomplete sequence at the end
+    /// of the stream is handled as invalid.
+    ///
+    /// All of `input` is consumed, unless an invalid sequence is reported
+    /// with [`InvalidPolicy::Error`]. In that case, the valid output before
+    /// it is appended to `out`, and `input` is left just after it, so
+    /// decoding can continue with the rest.
+    pub fn decode(
+        &mut self,
+        input: &mut &[u8],
+        out: &mut String,
+        last: bool,
+    ) -> Result<(), InvalidSequence> {
+        self.decode_marked(input, out, last, None)
+    }
+
+    /// Like `decode`, but if `marks` is present, also push the offset in the
+    /// input stream of the start of the output, and of the end of each unit
+    /// which isn't passed through unmodified, with the length of `out` at
+    /// that point, so that positions in the output can be mapped back to
+    /// positions in the input.
+    pub(crate) fn decode_marked(
+        &mut self,
+        input: &mut &[u8],
+        out: &mut String,
+        last: bool,
+        mut marks: Option<&mut Vec<(usize, u64)>>,
+    ) -> Result<(), InvalidSequence> {
+        if let Some(marks) = marks.as_deref_mut() {
+            marks.push((out.len(), self.read_total - u64::from(self.pending_len)));
+        }
+
+        if self.pending_len != 0 || matches!(self.bom, BomState::Pending) {
+            self.decode_pending(input, out, last, marks.as_deref_mut())?;
+            if self.pending_len != 0 {
+                return Ok(());
+            }
+        }
+
+        let bytes = *input;
+        let offset = self.read_total;
+        let (pos, result) = match self.decode_units(bytes, 0, bytes.len(), offset, out, last, marks)
+        {
+            Stop::Done(pos) => (pos, Ok(())),
+            Stop::Incomplete(pos) => {
+                self.hold(&bytes[pos..]);
+                (bytes.len(), Ok(()))
+            }
+            Stop::Invalid(pos, invalid) => (pos, Err(invalid)),
+        };
+        self.read_total += pos as u64;
+        *input = &bytes[pos..];
+        result
+    }
+
+    /// Return the encoding of the input, or `None` if the encoding is being
+    /// detected and not enough input has been decoded to tell yet.
+    #[inline]
+    pub fn encoding(&self) -> Option<Encoding> {
+        match self.bom {
+            BomState::Pending => None,
+            BomState::Keep | BomState::Stripped | BomState::Absent => Some(self.encoding),
+        }
+    }
+
+    /// When stripping a byte order mark or detecting the encoding, return
+    /// whether a byte order mark was present, or `None` if not enough input
+    /// has been decoded to tell yet, or if neither is enabled.
+    #[inline]
+    pub fn had_bom(&self) -> Option<bool> {
+        match self.bom {
+            BomState::Stripped => Some(true),
+            BomState::Absent => Some(false),
+            BomState::Keep | BomState::Pending => None,
+        }
+    }
+
+    /// Set how finely to divide invalid sequences.
+    #[inline]
+    pub(crate) fn set_granularity(&mut self, granularity: Granularity) {
+        self.granularity = granularity;
+    }
+
+    /// Set the text to replace invalid sequences with.
+    #[inline]
+    pub(crate) fn set_replacement(&mut self, replacement: Replacement) {
+        self.replacement = replacement;
+    }
+
+    /// Set whether to strip a leading UTF-8 byte order mark.
+    #[inline]
+    pub(crate) fn set_strip_bom(&mut self, strip_bom: bool) {
+        self.strip_bom = strip_bom;
+        self.reset_bom();
+    }
+
+    /// Set whether to detect the encoding from a leading byte order mark.
+    #[inline]
+    pub(crate) fn set_detect_encoding(&mut self, detect_encoding: bool) {
+        self.detect_encoding = detect_encoding;
+        self.reset_bom();
+    }
+
+    /// Start looking for a byte order mark, if either of the options which
+    /// need one are enabled.
+    #[inline]
+    fn reset_bom(&mut self) {
+        self.bom = if self.strip_bom || self.detect_encoding {
+            BomState::Pending
+        } else {
+            BomState::Keep
+        };
+    }
+
+    /// Set whether to accept CESU-8 surrogate pairs and Modified UTF-8's
+    /// encoding of U+0000.
+    #[inline]
+    pub(crate) fn set_modified_utf8(&mut self, modified_utf8: bool) {
+        self.modified_utf8 = modified_utf8;
+    }
+
+    /// Set a callback to report invalid sequences to.
+    #[inline]
+    pub(crate) fn set_on_invalid(
+        &mut self,
+        on_invalid: impl FnMut(&InvalidSequence) + Send + Sync + 'static,
+    ) {
+        self.on_invalid = Some(Box::new(on_invalid));
+    }
+
+    /// The smallest buffer which is guaranteed to make progress, holding
+    /// either a whole scalar value or a whole replacement.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn min_buf_len(&self) -> usize {
+        // UTF-8 needs at most 4 bytes per codepoint.
+        max(4, self.replacement.as_str().len())
+    }
+
+    /// Return the length of the longest prefix of `bytes`, the next input,
+    /// which can be passed through unmodified, and consume it. This lets
+    /// readers translate valid input in place, and only `decode` the rest.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn pass_through(&mut self, bytes: &[u8]) -> usize {
+        if self.pending_len != 0
+            || self.encoding != Encoding::Utf8
+            || matches!(self.bom, BomState::Pending)
+        {
+            return 0;
+        }
+        let valid_up_to = match str::from_utf8(bytes) {
+            Ok(_) => bytes.len(),
+            Err(error) => error.valid_up_to(),
+        };
+        let len = self
+            .find_escape(&bytes[..valid_up_to])
+            .unwrap_or(valid_up_to);
+        self.read_total += len as u64;
+        len
+    }
+
+    /// Return the number of bytes of held input which `take_held` puts
+    /// back, so that they can be passed through along with new input.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn held_len(&self) -> usize {
+        if self.encoding != Encoding::Utf8 || matches!(self.bom, BomState::Pending) {
+            return 0;
+        }
+        usize::from(self.pending_len)
+    }
+
+    /// Put the held input counted by `held_len` back into `buf`, to be
+    /// passed in again as the start of the next input.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn take_held(&mut self, buf: &mut [u8]) {
+        debug_assert_eq!(buf.len(), self.held_len());
+        buf.copy_from_slice(&self.pending[..buf.len()]);
+        self.pending_len = 0;
+        self.read_total -= buf.len() as u64;
+    }
+
+    /// Return the number of bytes of input which have been consumed, but
+    /// which are held until more input arrives.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn pending_len(&self) -> usize {
+        usize::from(self.pending_len)
+    }
+
+    /// Return the offset in the input stream of the next input to be
+    /// decoded, which doesn't count input held until more input arrives.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn offset(&self) -> u64 {
+        self.read_total - u64::from(self.pending_len)
+    }
+
+    /// Discard any held input, and continue at `offset` in the input stream,
+    /// as after a seek. A byte order mark is only looked for at the start.
+    #[cfg(feature = "std")]
+    pub(crate) fn reset(&mut self, offset: u64) {
+        self.pending_len = 0;
+        self.read_total = offset;
+        if offset == 0 {
+            if self.detect_encoding {
+                self.encoding = Encoding::Utf8;
+            }
+            self.reset_bom();
+        } else if let BomState::Pending = self.bom {
+            self.bom = BomState::Keep;
+        }
+    }
+
+    /// Discard any input which hasn't been translated yet.
+    #[cfg(feature = "layered-io")]
+    #[inline]
+    pub(crate) fn abandon(&mut self) {
+        self.pending_len = 0;
+    }
+
+    /// Hold `bytes`, which have been consumed, until more input arrives.
+    #[inline]
+    fn hold(&mut self, bytes: &[u8]) {
+        self.pending[..bytes.len()].copy_from_slice(bytes);
+        self.pending_len = bytes.len() as u8;
+    }
+
+    /// Decode any sequences which start in `pending`, using as much of
+    /// `input` as they need, and advance `input` past the bytes consumed.
+    #[cold]
+    fn decode_pending(
+        &mut self,
+        input: &mut &[u8],
+        out: &mut String,
+        last: bool,
+        mut marks: Option<&mut Vec<(usize, u64)>>,
+    ) -> Result<(), InvalidSequence> {
+        let pending_len = usize::from(self.pending_len);
+        let mut buf = [0; MAX_PENDING + MAX_UNIT];
+        let copy_len = min(input.len(), buf.len() - pending_len);
+        buf[..pending_len].copy_from_slice(&self.pending[..pending_len]);
+        buf[pending_len..pending_len + copy_len].copy_from_slice(&input[..copy_len]);
+        let bytes = &buf[..pending_len + copy_len];
+        let is_end = last && copy_len == input.len();
+        let offset = self.read_total - pending_len as u64;
+        self.pending_len = 0;
+
+        let mut pos = 0;
+        if let BomState::Pending = self.bom {
+            match self.process_bom(bytes, is_end) {
+                Some(bom_len) => {
+                    pos = bom_len;
+                    if let Some(marks) = marks.as_deref_mut() {
+                        marks.push((out.len(), offset + pos as u64));
+                    }
+                }
+                None => {
+                    // Hold on to the start of a possible byte order mark
+                    // until there's enough input to tell.
+                    debug_assert_eq!(copy_len, input.len());
+                    self.hold(bytes);
+                    self.read_total += copy_len as u64;
+                    *input = &input[copy_len..];
+                    return Ok(());
+                }
+            }
+        }
+
+        // Sequences starting in `pending` end within `MAX_UNIT` bytes, so
+        // `bytes` holds all of them unless it holds all of `input`.
+        let (pos, result) =
+            match self.decode_units(bytes, pos, pending_len, offset, out, is_end, marks) {
+                Stop::Done(pos) => (pos, Ok(())),
+                Stop::Incomplete(pos) => {
+                    debug_assert_eq!(copy_len, input.len());
+                    self.hold(&bytes[pos..]);
+                    (bytes.len(), Ok(()))
+                }
+                Stop::Invalid(pos, invalid) => {
+                    // Keep the rest of `pending`, if the error is inside it.
+                    if pos < pending_len {
+                        self.hold(&bytes[pos..pending_len]);
+                    }
+                    (pos, Err(invalid))
+                }
+            };
+        let consumed = pos.saturating_sub(pending_len);
+        self.read_total += consumed as u64;
+        *input = &input[consumed..];
+        result
+    }
+
+    /// Decode sequences from `bytes` starting at `pos`, appending the output
+    /// to `out`, until reaching `limit`. `offset` is the position of the
+    /// start of `bytes` in the input stream. See `decode_marked` for
+    /// `marks`.
+    #[allow(clippy::too_many_arguments)]
+    fn decode_units(
+        &mut self,
+        bytes: &[u8],
+        mut pos: usize,
+        limit: usize,
+        offset: u64,
+        out: &mut String,
+        is_end: bool,
+        mut marks: Option<&mut Vec<(usize, u64)>>,
+    ) -> Stop {
+        while pos < limit {
+            let rest = &bytes[pos..];
+            let (invalid_len, truncated) = match self.next_unit(rest, is_end) {
+                Unit::Valid(len) => {
+                    // Safety: `next_unit` validated these bytes.
+                    out.push_str(unsafe { str::from_utf8_unchecked(&rest[..len]) });
+                    pos += len;
+                    continue;
+                }
+                Unit::Char(c, len) => {
+                    out.push(c);
+                    pos += len;
+                    if let Some(marks) = marks.as_deref_mut() {
+                        marks.push((out.len(), offset + pos as u64));
+                    }
+                    continue;
+                }
+                Unit::Incomplete => return Stop::Incomplete(pos),
+                Unit::Invalid(len, truncated) => (len, truncated),
+            };
+
+            let invalid_len = if self.encoding == Encoding::Utf8 {
+                match self.policy {
+                    InvalidPolicy::Replace => out.push_str(self.replacement.as_str()),
+                    InvalidPolicy::Error | InvalidPolicy::Skip => {}
+                    InvalidPolicy::Escape | InvalidPolicy::Windows1252 | InvalidPolicy::Latin1 => {
+                        out.push(self.policy.translate_byte(rest[0]).unwrap())
+                    }
+                }
+                match (self.policy, self.granularity) {
+                    (policy, _) if policy.is_per_byte() => 1,
+                    (_, Granularity::PerByte) => 1,
+                    (_, Granularity::MaximalSubpart) => invalid_len,
+                }
+            } else {
+                // The policies which work a byte at a time are specific to
+                // UTF-8, so invalid UTF-16 or UTF-32 is replaced instead.
+                match self.policy {
+                    InvalidPolicy::Replace
+                    | InvalidPolicy::Escape
+                    | InvalidPolicy::Windows1252
+                    | InvalidPolicy::Latin1 => out.push_str(self.replacement.as_str()),
+                    InvalidPolicy::Error | InvalidPolicy::Skip => {}
+                }
+                invalid_len
+            };
+
+            let invalid = self.report_invalid(&rest[..invalid_len], offset + pos as u64, truncated);
+            pos += invalid_len;
+            if let Some(marks) = marks.as_deref_mut() {
+                marks.push((out.len(), offset + pos as u64));
+            }
+            if let Some(invalid) = invalid {
+                if self.policy == InvalidPolicy::Error {
+                    return Stop::Invalid(pos, invalid);
+                }
+            }
+        }
+        Stop::Done(pos)
+    }
+
+    /// Classify the sequence at the start of `bytes`, which is non-empty.
+    #[inline]
+    fn next_unit(&self, bytes: &[u8], is_end: bool) -> Unit {
+        if self.encoding != Encoding::Utf8 {
+            return match self.encoding.decode(bytes) {
+                Decoded::Char(c, len) => Unit::Char(c, len),
+                Decoded::Invalid(len) => Unit::Invalid(len, false),
+                Decoded::Incomplete if is_end => Unit::Invalid(bytes.len(), true),
+                Decoded::Incomplete => Unit::Incomplete,
+            };
+        }
+
+        let error = str::from_utf8(bytes).err();
+        let valid_up_to = error.map_or(bytes.len(), |error| error.valid_up_to());
+        match self.find_escape(&bytes[..valid_up_to]) {
+            // Escape code points are escaped a byte at a time.
+            Some(0) => return Unit::Invalid(1, false),
+            Some(escape) => return Unit::Valid(escape),
+            None if valid_up_to != 0 => return Unit::Valid(valid_up_to),
+            None => {}
+        }
+
+        if self.modified_utf8 {
+            match decode_modified_utf8(bytes) {
+                Decoded::Char(c, len) => return Unit::Char(c, len),
+                Decoded::Incomplete if !is_end => return Unit::Incomplete,
+                // At the end of the input, it's ordinary invalid input.
+                Decoded::Incomplete | Decoded::Invalid(_) => {}
+            }
+        }
+
+        match error.unwrap().error_len() {
+            Some(invalid_len) => Unit::Invalid(invalid_len, false),
+            None if is_end => Unit::Invalid(bytes.len(), true),
+            None => Unit::Incomplete,
+        }
+    }
+
+    /// Look for a byte order mark at the start of `bytes`, which holds the
+    /// start of the input, and when detecting the encoding, select the
+    /// encoding it identifies. Return the length of the byte order mark to
+    /// strip, or `None` if there isn't enough input to tell whether there's
+    /// a byte order mark yet.
+    #[cold]
+    fn process_bom(&mut self, bytes: &[u8], is_end: bool) -> Option<usize> {
+        let boms = if self.detect_encoding {
+            &Encoding::BOMS[..]
+        } else {
+            // Just the UTF-8 byte order mark.
+            &Encoding::BOMS[2..3]
+        };
+
+        for &(bom, encoding) in boms {
+            if bytes.len() < bom.len() && bom.starts_with(bytes) && !is_end {
+                return None;
+            }
+            if bytes.starts_with(bom) {
+                self.bom = BomState::Stripped;
+                self.encoding = encoding;
+                return Some(bom.len());
+            }
+        }
+
+        self.bom = BomState::Absent;
+        Some(0)
+    }
+
+    /// When escaping, find the first valid encoding of an escape code point
+    /// in `valid`, which must be valid UTF-8.
+    #[inline]
+    fn find_escape(&self, valid: &[u8]) -> Option<usize> {
+        if self.policy == InvalidPolicy::Escape {
+            find_escape(valid)
+        } else {
+            None
+        }
+    }
+
+    /// Report the invalid sequence `bytes`, found at `offset` in the input
+    /// stream, to the `on_invalid` callback. The sequence is returned if the
+    /// callback or the policy need it.
+    fn report_invalid(
+        &mut self,
+        bytes: &[u8],
+        offset: u64,
+        truncated: bool,
+    ) -> Option<InvalidSequence> {
+        if self.on_invalid.is_none() && self.policy != InvalidPolicy::Error {
+            return None;
+        }
+        let invalid = InvalidSequence {
+            offset,
+            bytes: bytes.to_vec(),
+            truncated,
+        };
+        if let Some(on_invalid) = &mut self.on_invalid {
+            on_invalid(&invalid);
+        }
+        Some(invalid)
+    }
+}
+
+/// The state of byte order mark stripping.
+enum BomState {
+    /// Don't strip a byte order mark.
+    Keep,
+    /// Strip a byte order mark, if there is one, but we haven't seen enough
+    /// input to tell yet.
+    Pending,
+    /// A byte order mark was stripped.
+    Stripped,
+    /// There was no byte order mark.
+    Absent,
+}
+
+/// The sequence at the start of some input.
+enum Unit {
+    /// Valid UTF-8 of the given length, which is passed through.
+    Valid(usize),
+    /// A translated scalar value, and the length of its encoding.
+    Char(char, usize),
+    /// An invalid sequence of the given length, and whether it's invalid
+    /// because it was truncated by the end of the input.
+    Invalid(usize, bool),
+    /// The input ends partway through a sequence.
+    Incomplete,
+}
+
+/// Where, and why, `decode_units` stopped.
+enum Stop {
+    /// It reached its limit.
+    Done(usize),
+    /// The input ends partway through a sequence starting here.
+    Incomplete(usize),
+    /// An invalid sequence ending here is reported as an error.
+    Invalid(usize, InvalidSequence),
+}
+
+impl Default for Utf8Decoder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Utf8Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut b = f.debug_struct("Utf8Decoder");
+        b.field("policy", &self.policy);
+        b.field("encoding", &self.encoding());
+        b.finish()
+    }
+}
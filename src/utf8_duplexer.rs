@@ -1,13 +1,13 @@
 use crate::utf8_input::Utf8Input;
 use crate::utf8_output::Utf8Output;
-use crate::{ReadStr, WriteStr};
+use crate::{Granularity, InvalidPolicy, InvalidSequence, ReadStr, Replacement, WriteStr};
 use duplex::{Duplex, HalfDuplex};
 #[cfg(windows)]
 use io_extras::os::windows::{
     AsHandleOrSocket, AsRawHandleOrSocket, AsReadWriteHandleOrSocket, BorrowedHandleOrSocket,
     RawHandleOrSocket,
 };
-use std::io::{self, Read, Write};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
 use std::{fmt, str};
 #[cfg(feature = "terminal-io")]
 use terminal_io::{DuplexTerminal, ReadTerminal, Terminal, TerminalColorSupport, WriteTerminal};
@@ -37,13 +37,65 @@ impl<Inner: HalfDuplex> Utf8Duplexer<Inner> {
     /// Construct a new instance of `Utf8Duplexer` wrapping `inner`.
     #[inline]
     pub fn new(inner: Inner) -> Self {
+        Self::with_policy(inner, InvalidPolicy::Replace)
+    }
+
+    /// Construct a new instance of `Utf8Duplexer` wrapping `inner`, which
+    /// handles invalid input sequences according to `policy`.
+    #[inline]
+    pub fn with_policy(inner: Inner, policy: InvalidPolicy) -> Self {
         Self {
             inner,
-            input: Utf8Input::new(),
+            input: Utf8Input::new(policy),
             output: Utf8Output::new(),
         }
     }
 
+    /// Set how many replacements are produced for each invalid sequence.
+    /// The default is [`Granularity::MaximalSubpart`].
+    #[inline]
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.input.decoder.set_granularity(granularity);
+        self
+    }
+
+    /// Set the text which invalid sequences are replaced with when using
+    /// [`InvalidPolicy::Replace`]. The default is U+FFFD.
+    ///
+    /// Reads with a buffer shorter than the replacement, or than 4 bytes,
+    /// are supported by reading ahead, but are less efficient.
+    #[inline]
+    pub fn replacement(mut self, replacement: impl Into<Replacement>) -> Self {
+        self.input.decoder.set_replacement(replacement.into());
+        self
+    }
+
+    /// Register a callback which is called with each invalid sequence
+    /// encountered in the input, before it is handled according to the
+    /// duplexer's [`InvalidPolicy`].
+    #[inline]
+    pub fn on_invalid(
+        mut self,
+        on_invalid: impl FnMut(&InvalidSequence) + Send + Sync + 'static,
+    ) -> Self {
+        self.input.decoder.set_on_invalid(on_invalid);
+        self
+    }
+
+    /// Set whether to translate the escapes produced by
+    /// [`InvalidPolicy::Escape`] back into the bytes they represent, so that
+    /// input read with a [`Utf8Reader`] using that policy is reproduced byte
+    /// for byte. With this enabled, the output is not necessarily valid
+    /// UTF-8.
+    ///
+    /// [`InvalidPolicy::Escape`]: crate::InvalidPolicy::Escape
+    /// [`Utf8Reader`]: crate::Utf8Reader
+    #[inline]
+    pub fn unescape(mut self, unescape: bool) -> Self {
+        self.output.set_unescape(unescape);
+        self
+    }
+
     /// Flush any pending output and return the inner output stream.
     #[inline]
     pub fn into_inner(self) -> io::Result<Inner> {
@@ -115,6 +167,26 @@ impl<Inner: HalfDuplex> ReadStr for Utf8Duplexer<Inner> {
     fn read_exact_str(&mut self, buf: &mut str) -> io::Result<()> {
         Utf8Input::read_exact_str(self, buf)
     }
+
+    #[inline]
+    fn read_str_append(&mut self, buf: &mut String, max: usize) -> io::Result<usize> {
+        Utf8Input::read_str_append(self, buf, max)
+    }
+
+    #[inline]
+    fn read_str_to_end(&mut self, buf: &mut String) -> io::Result<usize> {
+        Utf8Input::read_str_to_end(self, buf)
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        Utf8Input::read_char(self)
+    }
+
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        Utf8Input::peek_char(self)
+    }
 }
 
 #[cfg(feature = "layered-io")]
@@ -166,6 +238,11 @@ impl<Inner: HalfDuplex> Read for Utf8Duplexer<Inner> {
         Utf8Input::read(self, buf)
     }
 
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        Utf8Input::read_vectored(self, bufs)
+    }
+
     #[inline]
     fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
         Utf8Input::read_to_string(self, buf)
@@ -185,6 +262,16 @@ impl<Inner: HalfDuplex> WriteStr for Utf8Duplexer<Inner> {
     fn write_str(&mut self, s: &str) -> io::Result<()> {
         Utf8Output::write_str(self, s)
     }
+
+    #[inline]
+    fn write_str_partial(&mut self, s: &str) -> io::Result<usize> {
+        Utf8Output::write_str_partial(self, s)
+    }
+
+    #[inline]
+    fn write_str_vectored(&mut self, bufs: &[&str]) -> io::Result<()> {
+        Utf8Output::write_str_vectored(self, bufs)
+    }
 }
 
 impl<Inner: HalfDuplex> Duplex for Utf8Duplexer<Inner> {}
@@ -195,6 +282,11 @@ impl<Inner: HalfDuplex> Write for Utf8Duplexer<Inner> {
         Utf8Output::write(self, buf)
     }
 
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        Utf8Output::write_vectored(self, bufs)
+    }
+
     #[inline]
     fn flush(&mut self) -> io::Result<()> {
         Utf8Output::flush(self)
@@ -0,0 +1,130 @@
+use crate::InvalidSequence;
+use alloc::vec::Vec;
+use core::{error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+/// An error reported by this crate's readers and writers.
+///
+/// This is the inner error of the [`io::Error`]s they report for problems
+/// with the text itself, so it can be retrieved with
+/// `io::Error::get_ref().and_then(|e| e.downcast_ref::<Utf8Error>())`.
+///
+/// [`io::Error`]: std::io::Error
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Utf8Error {
+    /// An invalid sequence was read with [`InvalidPolicy::Error`], or was
+    /// written to a writer. Its offset is in the stream of bytes read or
+    /// written.
+    ///
+    /// [`InvalidPolicy::Error`]: crate::InvalidPolicy::Error
+    InvalidSequence(InvalidSequence),
+
+    /// A writer was flushed or closed while the start of a scalar value's
+    /// encoding written with `write` was still waiting to be completed.
+    /// Flushing or closing discards the incomplete bytes.
+    IncompleteAtFlush {
+        /// The offset of the incomplete encoding in the stream of bytes
+        /// written.
+        offset: u64,
+        /// The incomplete encoding.
+        bytes: Vec<u8>,
+    },
+
+    /// A writer was written to with `write_str` while the start of a scalar
+    /// value's encoding written with `write` was still waiting to be
+    /// completed. The incomplete bytes are kept, so the rest of the encoding
+    /// can still be written with `write`.
+    IncompleteAtWriteStr {
+        /// The offset of the incomplete encoding in the stream of bytes
+        /// written.
+        offset: u64,
+        /// The incomplete encoding.
+        bytes: Vec<u8>,
+    },
+
+    /// A writer was dropped while the start of a scalar value's encoding
+    /// written with `write` was still waiting to be completed. As `drop`
+    /// can't return an error, this is reported by panicking.
+    IncompleteAtDrop {
+        /// The offset of the incomplete encoding in the stream of bytes
+        /// written.
+        offset: u64,
+        /// The incomplete encoding.
+        bytes: Vec<u8>,
+    },
+
+    /// A buffer passed to a reader was too short to make progress.
+    BufferTooSmall {
+        /// The length of the buffer.
+        len: usize,
+        /// The length needed to make progress.
+        min_len: usize,
+    },
+}
+
+impl fmt::Display for Utf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSequence(invalid) => invalid.fmt(f),
+            Self::IncompleteAtFlush { offset, bytes } => {
+                write!(f, "incomplete UTF-8 encoding at flush, at byte {}:", offset)?;
+                write_bytes(f, bytes)
+            }
+            Self::IncompleteAtWriteStr { offset, bytes } => {
+                write!(
+                    f,
+                    "write_str while a UTF-8 encoding is incomplete, at byte {}:",
+                    offset
+                )?;
+                write_bytes(f, bytes)
+            }
+            Self::IncompleteAtDrop { offset, bytes } => {
+                write!(
+                    f,
+                    "output text stream not ended on UTF-8 boundary, at byte {}:",
+                    offset
+                )?;
+                write_bytes(f, bytes)
+            }
+            Self::BufferTooSmall { len, min_len } => write!(
+                f,
+                "buffer of {} bytes is too short; at least {} bytes are needed",
+                len, min_len
+            ),
+        }
+    }
+}
+
+/// Write `bytes` in hexadecimal.
+pub(crate) fn write_bytes(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    for byte in bytes {
+        write!(f, " {:02x}", byte)?;
+    }
+    Ok(())
+}
+
+impl error::Error for Utf8Error {}
+
+impl From<InvalidSequence> for Utf8Error {
+    #[inline]
+    fn from(invalid: InvalidSequence) -> Self {
+        Self::InvalidSequence(invalid)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Utf8Error> for io::Error {
+    #[inline]
+    fn from(error: Utf8Error) -> Self {
+        let kind = match error {
+            Utf8Error::BufferTooSmall { .. } => io::ErrorKind::InvalidInput,
+            Utf8Error::InvalidSequence(_)
+            | Utf8Error::IncompleteAtFlush { .. }
+            | Utf8Error::IncompleteAtWriteStr { .. }
+            | Utf8Error::IncompleteAtDrop { .. } => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error)
+    }
+}
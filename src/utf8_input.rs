@@ -1,21 +1,27 @@
-use crate::{Utf8Duplexer, Utf8Reader};
+use crate::read_str::{read_into_spare_capacity, read_to_end_with};
+use crate::{
+    Encoding, InvalidPolicy, InvalidSequence, Utf8Decoder, Utf8Duplexer, Utf8Error, Utf8Reader,
+    DEFAULT_BUF_SIZE,
+};
 use duplex::Duplex;
-use std::cmp::min;
-use std::io::{self, copy, repeat, Cursor, Read, Write};
-use std::str;
 #[cfg(feature = "layered-io")]
-use {
-    layered_io::{HalfDuplexLayered, ReadLayered, Status},
-    std::cmp::max,
-};
+use layered_io::{HalfDuplexLayered, ReadLayered, Status};
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::io::{self, IoSliceMut, Read, Seek, SeekFrom, Write};
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+use std::task::Poll;
+
+/// The size of the buffer used to read a `char` at a time.
+const CHAR_BUF_SIZE: usize = 1024;
 
 pub(crate) trait Utf8ReaderInternals<Inner: Read>: Read {
     fn impl_(&mut self) -> &mut Utf8Input;
     #[cfg(feature = "layered-io")]
+    fn impl_ref(&self) -> &Utf8Input;
+    #[cfg(feature = "layered-io")]
     fn inner(&self) -> &Inner;
     fn inner_mut(&mut self) -> &mut Inner;
-    #[cfg(feature = "layered-io")]
-    fn into_inner(self) -> Inner;
 }
 
 #[cfg(feature = "layered-io")]
@@ -29,6 +35,11 @@ impl<Inner: Read> Utf8ReaderInternals<Inner> for Utf8Reader<Inner> {
         &mut self.input
     }
 
+    #[cfg(feature = "layered-io")]
+    fn impl_ref(&self) -> &Utf8Input {
+        &self.input
+    }
+
     #[cfg(feature = "layered-io")]
     fn inner(&self) -> &Inner {
         &self.inner
@@ -37,11 +48,6 @@ impl<Inner: Read> Utf8ReaderInternals<Inner> for Utf8Reader<Inner> {
     fn inner_mut(&mut self) -> &mut Inner {
         &mut self.inner
     }
-
-    #[cfg(feature = "layered-io")]
-    fn into_inner(self) -> Inner {
-        self.inner
-    }
 }
 
 #[cfg(feature = "layered-io")]
@@ -52,6 +58,11 @@ impl<Inner: Duplex + Read + Write> Utf8ReaderInternals<Inner> for Utf8Duplexer<I
         &mut self.input
     }
 
+    #[cfg(feature = "layered-io")]
+    fn impl_ref(&self) -> &Utf8Input {
+        &self.input
+    }
+
     #[cfg(feature = "layered-io")]
     fn inner(&self) -> &Inner {
         &self.inner
@@ -60,31 +71,155 @@ impl<Inner: Duplex + Read + Write> Utf8ReaderInternals<Inner> for Utf8Duplexer<I
     fn inner_mut(&mut self) -> &mut Inner {
         &mut self.inner
     }
-
-    #[cfg(feature = "layered-io")]
-    fn into_inner(self) -> Inner {
-        self.inner
-    }
 }
 
 #[cfg(feature = "layered-io")]
 impl<Inner: HalfDuplexLayered> Utf8ReaderInternalsLayered<Inner> for Utf8Duplexer<Inner> {}
 
 pub(crate) struct Utf8Input {
-    /// A queue of bytes which have not been read but which have not been
-    /// translated into the output yet.
-    overflow: Vec<u8>,
+    /// The decoding state machine.
+    pub(crate) decoder: Utf8Decoder,
+
+    /// Output which has been read ahead, to read a `char` at a time or
+    /// because it didn't fit in the caller's buffer, and which is returned
+    /// before any new output.
+    decoded: String,
+
+    /// The start of the part of `decoded` which hasn't been returned yet.
+    decoded_pos: usize,
+
+    /// Invalid sequences found while reading ahead, with their positions in
+    /// `decoded`, which are reported once the output before them has been
+    /// returned.
+    errors: VecDeque<(usize, InvalidSequence)>,
+
+    /// Positions in `decoded`, with the offsets in the input stream they
+    /// were decoded from, so that output which has been read ahead can be
+    /// mapped back to a position in the input. See `decode_marked`.
+    marks: Vec<(usize, u64)>,
+
+    /// The encoding of a scalar value which was split by a `read` into a
+    /// buffer too short to hold all of it.
+    split: [u8; 4],
+
+    /// The range of `split` which hasn't been returned yet.
+    split_pos: usize,
+    split_len: usize,
+
+    /// Whether seeking skips forward to the next scalar value boundary.
+    seek_to_char_boundary: bool,
 }
 
 impl Utf8Input {
     /// Construct a new instance of `Utf8Input`.
     #[inline]
-    pub(crate) const fn new() -> Self {
+    pub(crate) const fn new(policy: InvalidPolicy) -> Self {
         Self {
-            overflow: Vec::new(),
+            decoder: Utf8Decoder::with_policy(policy),
+            decoded: String::new(),
+            decoded_pos: 0,
+            errors: VecDeque::new(),
+            marks: Vec::new(),
+            split: [0; 4],
+            split_pos: 0,
+            split_len: 0,
+            seek_to_char_boundary: false,
         }
     }
 
+    /// Set whether seeking skips forward to the next scalar value boundary.
+    #[inline]
+    pub(crate) fn set_seek_to_char_boundary(&mut self, seek_to_char_boundary: bool) {
+        self.seek_to_char_boundary = seek_to_char_boundary;
+    }
+
+    /// Seek the inner stream, discarding any input held or read ahead, and
+    /// if enabled, skip forward to the next scalar value boundary at the new
+    /// position.
+    pub(crate) fn seek<Inner: Read + Seek>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+        pos: SeekFrom,
+    ) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Current(offset) => {
+                // The inner stream is ahead of the reader by the input which
+                // has been consumed but not returned yet.
+                let impl_ = internals.impl_();
+                let ahead =
+                    impl_.decoder.offset() - impl_.offset() + impl_.decoder.pending_len() as u64;
+                SeekFrom::Current(offset - ahead as i64)
+            }
+            pos => pos,
+        };
+        let pos = internals.inner_mut().seek(pos)?;
+        internals.impl_().reset(pos);
+
+        if !internals.impl_().seek_to_char_boundary || pos == 0 {
+            return Ok(pos);
+        }
+
+        let mut bytes = [0; 3];
+        let mut len = 0;
+        while len < bytes.len() {
+            match internals.inner_mut().read(&mut bytes[len..]) {
+                Ok(0) => break,
+                Ok(size) => len += size,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    // Put the inner stream back where the reader is.
+                    if len != 0 {
+                        internals.inner_mut().seek(SeekFrom::Start(pos))?;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        let encoding = internals.impl_().decoder.encoding().unwrap_or_default();
+        let skip = char_boundary_skip(encoding, pos, &bytes[..len]);
+        let pos = internals
+            .inner_mut()
+            .seek(SeekFrom::Start(pos + skip as u64))?;
+        internals.impl_().reset(pos);
+        Ok(pos)
+    }
+
+    /// Discard any input held or read ahead, and continue at `pos` in the
+    /// input stream.
+    fn reset(&mut self, pos: u64) {
+        self.decoder.reset(pos);
+        self.decoded.clear();
+        self.decoded_pos = 0;
+        self.errors.clear();
+        self.marks.clear();
+        self.split_pos = 0;
+        self.split_len = 0;
+    }
+
+    /// Return the offset in the input stream of the next output to be
+    /// returned.
+    fn offset(&self) -> u64 {
+        let split_rest = self.split_len - self.split_pos;
+        if split_rest == 0 {
+            match self.errors.front() {
+                Some((pos, invalid)) if *pos == self.decoded_pos => return invalid.offset(),
+                _ if self.decoded_pos == self.decoded.len() => return self.decoder.offset(),
+                _ => {}
+            }
+        }
+
+        // Output between marks was passed through unmodified, so it's the
+        // same length as its input. The rest of a split scalar value
+        // hasn't been returned yet.
+        let pos = self.decoded_pos - split_rest;
+        let i = self.marks.partition_point(|(mark_pos, _)| *mark_pos <= pos);
+        let (mark_pos, mark_offset) = self.marks[i - 1];
+        let end = self
+            .marks
+            .get(i)
+            .map_or(self.decoder.offset(), |(_, offset)| *offset);
+        min(mark_offset + (pos - mark_pos) as u64, end)
+    }
+
     /// Like `read_with_status` but produces the result in a `str`. Be sure to
     /// check the `size` field of the return value to see how many bytes were
     /// written.
@@ -93,8 +228,13 @@ impl Utf8Input {
         internals: &mut impl Utf8ReaderInternals<Inner>,
         buf: &mut str,
     ) -> io::Result<usize> {
-        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
-        internals.read(unsafe { buf.as_bytes_mut() })
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`,
+        // as long as we overwrite the rest of the buffer, which may hold raw
+        // input.
+        let bytes = unsafe { buf.as_bytes_mut() };
+        let result = Self::read_whole(internals, bytes);
+        bytes[*result.as_ref().unwrap_or(&0)..].fill(b'\0');
+        result
     }
 
     /// Like `read_exact` but produces the result in a `str`.
@@ -103,8 +243,72 @@ impl Utf8Input {
         internals: &mut impl Utf8ReaderInternals<Inner>,
         buf: &mut str,
     ) -> io::Result<()> {
-        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
-        internals.read_exact(unsafe { buf.as_bytes_mut() })
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`,
+        // as long as we overwrite the buffer if we fail to fill it.
+        let bytes = unsafe { buf.as_bytes_mut() };
+        let mut nread = 0;
+        let result = loop {
+            if nread == bytes.len() {
+                break Ok(());
+            }
+            match Self::read_whole(internals, &mut bytes[nread..]) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    ))
+                }
+                Ok(size) => nread += size,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+        if result.is_err() {
+            bytes.fill(b'\0');
+        }
+        result
+    }
+
+    /// Read a single `char`, returning `None` at the end of the stream.
+    #[inline]
+    pub(crate) fn read_char<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+    ) -> io::Result<Option<char>> {
+        let c = Self::peek_char(internals)?;
+        if let Some(c) = c {
+            internals.impl_().decoded_pos += c.len_utf8();
+        }
+        Ok(c)
+    }
+
+    /// Return the next `char` without consuming it, or `None` at the end of
+    /// the stream.
+    pub(crate) fn peek_char<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+    ) -> io::Result<Option<char>> {
+        internals.impl_().check_not_split()?;
+        loop {
+            let impl_ = internals.impl_();
+            if let Some(invalid) = impl_.take_error() {
+                return Err(invalid.into());
+            }
+            if let Some(c) = impl_.decoded[impl_.decoded_pos..].chars().next() {
+                return Ok(Some(c));
+            }
+
+            // Read ahead into `decoded`.
+            let mut buf = [0; CHAR_BUF_SIZE];
+            let size = match internals.inner_mut().read(&mut buf) {
+                Ok(size) => size,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            let impl_ = internals.impl_();
+            impl_.decode(&buf[..size], size == 0);
+            if size == 0 && impl_.is_drained() {
+                return Ok(None);
+            }
+        }
     }
 
     /// Like `read_with_status` but produces the result in a `str`. Be sure to
@@ -116,8 +320,14 @@ impl Utf8Input {
         internals: &mut impl Utf8ReaderInternalsLayered<Inner>,
         buf: &mut str,
     ) -> io::Result<(usize, Status)> {
-        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
-        let (size, status) = internals.read_with_status(unsafe { buf.as_bytes_mut() })?;
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`,
+        // as long as we overwrite the rest of the buffer, which may hold raw
+        // input.
+        internals.impl_().check_not_split()?;
+        let bytes = unsafe { buf.as_bytes_mut() };
+        let result = internals.read_with_status(bytes);
+        bytes[result.as_ref().map_or(0, |(size, _status)| *size)..].fill(b'\0');
+        let (size, status) = result?;
 
         debug_assert!(buf.is_char_boundary(size));
 
@@ -133,8 +343,15 @@ impl Utf8Input {
         internals: &mut impl Utf8ReaderInternalsLayered<Inner>,
         buf: &mut str,
     ) -> io::Result<Status> {
-        // Safety: This is a UTF-8 stream so we can read directly into a `str`.
-        internals.read_exact_using_status(unsafe { buf.as_bytes_mut() })
+        // Safety: This is a UTF-8 stream so we can read directly into a `str`,
+        // as long as we overwrite the buffer if we fail to fill it.
+        internals.impl_().check_not_split()?;
+        let bytes = unsafe { buf.as_bytes_mut() };
+        let result = internals.read_exact_using_status(bytes);
+        if result.is_err() {
+            bytes.fill(b'\0');
+        }
+        result
     }
 
     #[cfg(feature = "layered-io")]
@@ -142,166 +359,201 @@ impl Utf8Input {
         internals: &mut impl Utf8ReaderInternalsLayered<Inner>,
         buf: &mut [u8],
     ) -> io::Result<(usize, Status)> {
-        let (nread, done) = Self::process_old_data(internals, buf)?;
-        if done {
-            return Ok((nread, Status::active()));
+        if internals.impl_().is_split() {
+            return Ok((internals.impl_().drain_split(buf), Status::active()));
         }
 
-        let (size, status) = internals.inner_mut().read_with_status(&mut buf[nread..])?;
+        if let Some(nread) = internals.impl_().process_old_data(buf)? {
+            return Ok((nread, Status::active()));
+        }
 
-        let (nread, done) = Self::process_new_data(internals, buf, nread, size, status.is_end())?;
-        Ok((nread, if done { status } else { Status::active() }))
+        let held_len = internals.impl_().held_len(buf);
+        let (size, status) = internals
+            .inner_mut()
+            .read_with_status(&mut buf[held_len..])?;
+
+        let impl_ = internals.impl_();
+        impl_.take_held(&mut buf[..held_len]);
+        let nread = impl_.process_new_data(buf, held_len + size, status.is_end())?;
+        Ok((
+            nread,
+            if impl_.is_drained() {
+                status
+            } else {
+                Status::active()
+            },
+        ))
     }
 
-    fn process_old_data<Inner: Read>(
-        internals: &mut impl Utf8ReaderInternals<Inner>,
-        buf: &mut [u8],
-    ) -> io::Result<(usize, bool)> {
+    /// Produce output which was read ahead by previous reads, or report an
+    /// invalid sequence found while reading ahead. Return `None` if there's
+    /// none left.
+    fn process_old_data(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
         // To ensure we can always make progress, callers should always use a
-        // buffer of at least 4 bytes.
-        if buf.len() < 4 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "buffer for reading from Utf8Reader must be at least 4 bytes long",
-            ));
+        // buffer of at least 4 bytes, and long enough for the replacement.
+        let min_len = self.min_buf_len();
+        if buf.len() < min_len {
+            return Err(Utf8Error::BufferTooSmall {
+                len: buf.len(),
+                min_len,
+            }
+            .into());
         }
 
-        let mut nread = 0;
+        if let Some(invalid) = self.take_error() {
+            return Err(invalid.into());
+        }
+        if self.decoded_pos < self.decoded.len() {
+            return Ok(Some(self.drain_decoded(buf)));
+        }
+        Ok(None)
+    }
+
+    /// Produce output from the `len` bytes of new input in `buf`. Valid
+    /// input is passed through in place, and the rest is decoded into
+    /// `decoded`, and copied back into `buf` as far as it fits.
+    fn process_new_data(&mut self, buf: &mut [u8], len: usize, is_end: bool) -> io::Result<usize> {
+        let valid_len = self.decoder.pass_through(&buf[..len]);
+        if valid_len == len && !is_end {
+            return Ok(len);
+        }
 
-        if !internals.impl_().overflow.is_empty() {
-            nread += internals
-                .impl_()
-                .process_overflow(&mut buf[nread..], IncompleteHow::Include)
-                .unwrap();
-            if !internals.impl_().overflow.is_empty() {
-                return Ok((nread, true));
+        self.decode(&buf[valid_len..len], is_end);
+        let nread = valid_len + self.drain_decoded(&mut buf[valid_len..]);
+        if nread == 0 {
+            if let Some(invalid) = self.take_error() {
+                return Err(invalid.into());
             }
         }
+        Ok(nread)
+    }
 
-        Ok((nread, false))
+    /// Decode `input` into `decoded`, recording any errors.
+    fn decode(&mut self, mut input: &[u8], is_end: bool) {
+        if self.is_drained() {
+            self.decoded.clear();
+            self.decoded_pos = 0;
+            self.marks.clear();
+        }
+        while let Err(invalid) =
+            self.decoder
+                .decode_marked(&mut input, &mut self.decoded, is_end, Some(&mut self.marks))
+        {
+            self.errors.push_back((self.decoded.len(), invalid));
+        }
     }
 
-    pub(crate) fn process_new_data<Inner: Read>(
-        internals: &mut impl Utf8ReaderInternals<Inner>,
-        buf: &mut [u8],
-        mut nread: usize,
-        size: usize,
-        is_end: bool,
-    ) -> io::Result<(usize, bool)> {
-        nread += size;
-
-        // We may have overwritten part of a codepoint; overwrite the rest of
-        // the buffer.
-        // TODO: Use [`fill`] when it becomes available:
-        // https://doc.rust-lang.org/std/primitive.slice.html#method.fill
-        copy(
-            &mut repeat(b'\0').take((buf.len() - nread) as u64),
-            &mut Cursor::new(&mut buf[nread..]),
-        )
-        .unwrap();
-
-        match str::from_utf8(&buf[..nread]) {
-            Ok(_) => Ok((nread, true)),
-            Err(error) => {
-                let (valid, after_valid) = buf[..nread].split_at(error.valid_up_to());
-                nread = valid.len();
-
-                assert!(internals.impl_().overflow.is_empty());
-                internals.impl_().overflow.extend_from_slice(after_valid);
-
-                let incomplete_how = if is_end {
-                    IncompleteHow::Replace
-                } else {
-                    IncompleteHow::Exclude
-                };
-                nread += internals
-                    .impl_()
-                    .process_overflow(&mut buf[nread..], incomplete_how)
-                    .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "invalid UTF-8"))?;
-                Ok((nread, internals.impl_().overflow.is_empty()))
+    /// Return the number of bytes of held input to put back at the start of
+    /// `buf`, leaving room for new input after them.
+    #[inline]
+    fn held_len(&self, buf: &[u8]) -> usize {
+        let held_len = self.decoder.held_len();
+        if held_len < buf.len() {
+            held_len
+        } else {
+            0
+        }
+    }
+
+    /// Put the held input counted by `held_len` back at the start of `buf`.
+    #[inline]
+    fn take_held(&mut self, buf: &mut [u8]) {
+        if !buf.is_empty() {
+            self.decoder.take_held(buf);
+        }
+    }
+
+    /// Return whether all output which was read ahead has been returned.
+    #[inline]
+    fn is_drained(&self) -> bool {
+        self.decoded_pos == self.decoded.len() && self.errors.is_empty()
+    }
+
+    /// If reading has reached an invalid sequence found while reading ahead,
+    /// return it.
+    #[inline]
+    fn take_error(&mut self) -> Option<InvalidSequence> {
+        match self.errors.front() {
+            Some((pos, _)) if *pos == self.decoded_pos => {
+                self.errors.pop_front().map(|(_, invalid)| invalid)
             }
+            _ => None,
         }
     }
 
+    /// The smallest buffer which is guaranteed to make progress.
+    #[inline]
+    fn min_buf_len(&self) -> usize {
+        self.decoder.min_buf_len()
+    }
+
     #[cfg(feature = "layered-io")]
     #[inline]
     pub(crate) fn minimum_buffer_size<Inner: ReadLayered>(
         internals: &impl Utf8ReaderInternals<Inner>,
     ) -> usize {
-        // UTF-8 needs at most 4 bytes per codepoint.
-        max(4, internals.inner().minimum_buffer_size())
+        max(
+            internals.impl_ref().min_buf_len(),
+            internals.inner().minimum_buffer_size(),
+        )
     }
 
-    /// If normal reading encounters invalid bytes, the data is copied into
-    /// `internals.impl_().overflow` as it may need to expand to make room for
-    /// the U+FFFD's, and we may need to hold on to some of it until the next
-    /// `read` call.
-    ///
-    /// TODO: This code could be significantly optimized.
-    #[cold]
-    fn process_overflow(&mut self, buf: &mut [u8], incomplete_how: IncompleteHow) -> Option<usize> {
-        let mut nread = 0;
+    /// Copy as much output which was read ahead into `buf` as will fit
+    /// without splitting a scalar value or passing an invalid sequence.
+    /// Return the number of bytes copied.
+    fn drain_decoded(&mut self, buf: &mut [u8]) -> usize {
+        let end = self
+            .errors
+            .front()
+            .map_or(self.decoded.len(), |(pos, _)| *pos);
+        let available = &self.decoded[self.decoded_pos..end];
+        let mut len = min(available.len(), buf.len());
+        while !available.is_char_boundary(len) {
+            len -= 1;
+        }
+        buf[..len].copy_from_slice(&available.as_bytes()[..len]);
+        self.decoded_pos += len;
+        len
+    }
 
-        loop {
-            let num = min(buf[nread..].len(), self.overflow.len());
-            match str::from_utf8(&self.overflow[..num]) {
-                Ok(_) => {
-                    buf[nread..nread + num].copy_from_slice(&self.overflow[..num]);
-                    self.overflow.copy_within(num.., 0);
-                    self.overflow.resize(self.overflow.len() - num, 0);
-                    nread += num;
-                }
-                Err(error) => {
-                    let (valid, after_valid) = self.overflow[..num].split_at(error.valid_up_to());
-                    let valid_len = valid.len();
-                    let after_valid_len = after_valid.len();
-                    buf[nread..nread + valid_len].copy_from_slice(valid);
-                    self.overflow.copy_within(valid_len.., 0);
-                    self.overflow.resize(self.overflow.len() - valid_len, 0);
-                    nread += valid_len;
-
-                    if let Some(invalid_sequence_length) = error.error_len() {
-                        if '\u{fffd}'.len_utf8() <= buf[nread..].len() {
-                            nread += '\u{fffd}'.encode_utf8(&mut buf[nread..]).len();
-                            self.overflow.copy_within(invalid_sequence_length.., 0);
-                            self.overflow
-                                .resize(self.overflow.len() - invalid_sequence_length, 0);
-                            continue;
-                        }
-                    } else {
-                        match incomplete_how {
-                            IncompleteHow::Replace => {
-                                if '\u{fffd}'.len_utf8() <= buf[nread..].len() {
-                                    nread += '\u{fffd}'.encode_utf8(&mut buf[nread..]).len();
-                                    self.overflow.clear();
-                                } else if self.overflow.is_empty() {
-                                    return None;
-                                }
-                            }
-                            IncompleteHow::Include if after_valid_len == self.overflow.len() => {
-                                if !buf[nread..].is_empty() {
-                                    let num = min(buf[nread..].len(), after_valid_len);
-                                    buf[nread..nread + num].copy_from_slice(&self.overflow[..num]);
-                                    nread += num;
-                                    self.overflow.copy_within(num.., 0);
-                                    self.overflow.resize(self.overflow.len() - num, 0);
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-            break;
+    /// Return whether a scalar value's encoding has been partially returned.
+    #[inline]
+    fn is_split(&self) -> bool {
+        self.split_pos < self.split_len
+    }
+
+    /// Fail if a scalar value's encoding has been partially returned, as the
+    /// rest of it can't be returned in a `str`.
+    fn check_not_split(&self) -> io::Result<()> {
+        if self.is_split() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a previous read split a scalar value's encoding; read the rest of it with `read`",
+            ));
         }
+        Ok(())
+    }
 
-        Some(nread)
+    /// Copy as much of the rest of `split` as fits into `buf`, and return the
+    /// number of bytes copied.
+    fn drain_split(&mut self, buf: &mut [u8]) -> usize {
+        let available = &self.split[self.split_pos..self.split_len];
+        let len = min(available.len(), buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.split_pos += len;
+        len
     }
 
     #[cfg(feature = "layered-io")]
     #[inline]
     pub(crate) fn abandon<Inner: ReadLayered>(internals: &mut impl Utf8ReaderInternals<Inner>) {
-        internals.impl_().overflow.clear();
+        internals.impl_().decoder.abandon();
+        internals.impl_().decoded.clear();
+        internals.impl_().decoded_pos = 0;
+        internals.impl_().errors.clear();
+        internals.impl_().marks.clear();
+        internals.impl_().split_len = 0;
+        internals.impl_().split_pos = 0;
         internals.inner_mut().abandon()
     }
 
@@ -316,53 +568,260 @@ impl Utf8Input {
         )
     }
 
-    #[inline]
+    /// Read into `buf`. If `buf` is too short to hold the next scalar value,
+    /// return as much of its encoding as fits, and return the rest from
+    /// subsequent reads.
     pub(crate) fn read<Inner: Read>(
         internals: &mut impl Utf8ReaderInternals<Inner>,
         buf: &mut [u8],
     ) -> io::Result<usize> {
-        let (nread, done) = Self::process_old_data(internals, buf)?;
-        if done {
-            return Ok(nread);
+        let impl_ = internals.impl_();
+        if impl_.is_split() {
+            return Ok(impl_.drain_split(buf));
+        }
+        if buf.len() < impl_.min_buf_len() {
+            return Self::read_short(internals, buf, true);
         }
+        Self::read_long(internals, buf)
+    }
 
-        let (size, is_end) = match internals.inner_mut().read(&mut buf[nread..]) {
-            Ok(0) => (0, true),
-            Ok(size) => (size, false),
-            Err(err) if err.kind() == io::ErrorKind::Interrupted => (0, false),
-            Err(err) => return Err(err),
+    /// Read into `bufs`, with a single read from the inner stream. No scalar
+    /// value's encoding straddles two buffers; each buffer is filled with
+    /// whole scalar values, and the next one is only used if it's full.
+    pub(crate) fn read_vectored<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> io::Result<usize> {
+        let first = match bufs.iter_mut().position(|buf| !buf.is_empty()) {
+            Some(first) => first,
+            None => return Ok(0),
         };
+        let bufs = &mut bufs[first..];
+
+        // A short first buffer may need a scalar value to be split, which
+        // `read` knows how to do.
+        let impl_ = internals.impl_();
+        if impl_.is_split() || bufs[0].len() < impl_.min_buf_len() {
+            return Self::read(internals, &mut bufs[0]);
+        }
+        if let Some(invalid) = impl_.take_error() {
+            return Err(invalid.into());
+        }
+
+        // Read raw input into `bufs`, and decode it all into `decoded`.
+        let mut is_end = false;
+        if impl_.decoded_pos == impl_.decoded.len() {
+            let size = internals.inner_mut().read_vectored(bufs)?;
+            let impl_ = internals.impl_();
+            let mut remaining = size;
+            for buf in bufs.iter() {
+                let len = min(buf.len(), remaining);
+                impl_.decode(&buf[..len], false);
+                remaining -= len;
+            }
+            is_end = size == 0;
+            if is_end {
+                impl_.decode(&[], true);
+            }
+        }
 
-        let (nread, done) = Self::process_new_data(internals, buf, nread, size, is_end)?;
+        let impl_ = internals.impl_();
+        let mut nread = 0;
+        for buf in bufs.iter_mut() {
+            let len = impl_.drain_decoded(buf);
+            nread += len;
+            if len != buf.len() {
+                break;
+            }
+        }
+        match nread {
+            0 if is_end && impl_.is_drained() => Ok(0),
+            0 => match impl_.take_error() {
+                Some(invalid) => Err(invalid.into()),
+                None => Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "read zero bytes from stream",
+                )),
+            },
+            nread => Ok(nread),
+        }
+    }
 
-        match (nread, done) {
-            (0, true) => Ok(0),
-            (0, false) => Err(io::Error::new(
+    /// Like `read`, but never splits a scalar value's encoding, so that the
+    /// output can be used as a `str`.
+    fn read_whole<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        let impl_ = internals.impl_();
+        impl_.check_not_split()?;
+        if buf.len() < impl_.min_buf_len() {
+            return Self::read_short(internals, buf, false);
+        }
+        Self::read_long(internals, buf)
+    }
+
+    /// Read into a buffer which may be too short to hold a whole scalar
+    /// value or replacement, by reading ahead into `decoded`.
+    fn read_short<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+        buf: &mut [u8],
+        split: bool,
+    ) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let c = match Self::peek_char(internals)? {
+            Some(c) => c,
+            None => return Ok(0),
+        };
+
+        let impl_ = internals.impl_();
+        match impl_.drain_decoded(buf) {
+            0 if split => {
+                impl_.decoded_pos += c.len_utf8();
+                impl_.split_len = c.encode_utf8(&mut impl_.split).len();
+                impl_.split_pos = 0;
+                Ok(impl_.drain_split(buf))
+            }
+            0 => Err(Utf8Error::BufferTooSmall {
+                len: buf.len(),
+                min_len: c.len_utf8(),
+            }
+            .into()),
+            nread => Ok(nread),
+        }
+    }
+
+    /// Read into a buffer which is long enough to always make progress.
+    fn read_long<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        if let Some(nread) = internals.impl_().process_old_data(buf)? {
+            return Ok(nread);
+        }
+
+        let held_len = internals.impl_().held_len(buf);
+        let (size, is_end) = match internals.inner_mut().read(&mut buf[held_len..])? {
+            0 => (0, true),
+            size => (size, false),
+        };
+
+        let impl_ = internals.impl_();
+        impl_.take_held(&mut buf[..held_len]);
+        match impl_.process_new_data(buf, held_len + size, is_end)? {
+            0 if is_end => Ok(0),
+            0 => Err(io::Error::new(
                 io::ErrorKind::Interrupted,
                 "read zero bytes from stream",
             )),
-            (_, _) => Ok(nread),
+            nread => Ok(nread),
         }
     }
 
+    /// Read into `buf`, polling the inner stream with `poll_inner`. Unlike
+    /// `read`, `buf` must be long enough to always make progress.
+    ///
+    /// Input which has been consumed from the inner stream is never held in
+    /// `buf` while it's polled, so nothing is lost if the caller's future is
+    /// dropped.
+    #[cfg(any(feature = "tokio", feature = "futures-io"))]
+    pub(crate) fn poll_read(
+        &mut self,
+        buf: &mut [u8],
+        mut poll_inner: impl FnMut(&mut [u8]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if let Some(nread) = self.process_old_data(buf)? {
+                return Poll::Ready(Ok(nread));
+            }
+
+            let held_len = self.held_len(buf);
+            let (size, is_end) = match poll_inner(&mut buf[held_len..]) {
+                Poll::Ready(Ok(0)) => (0, true),
+                Poll::Ready(Ok(size)) => (size, false),
+                Poll::Ready(Err(err)) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            self.take_held(&mut buf[..held_len]);
+            let nread = self.process_new_data(buf, held_len + size, is_end)?;
+            if nread != 0 || is_end {
+                return Poll::Ready(Ok(nread));
+            }
+        }
+    }
+
+    /// Read up to `max` bytes, appending them to `buf`.
+    #[inline]
+    pub(crate) fn read_str_append<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+        buf: &mut String,
+        max: usize,
+    ) -> io::Result<usize> {
+        // Safety: Our `read` implementation only produces complete UTF-8
+        // encodings.
+        unsafe {
+            read_into_spare_capacity(buf, max, &mut 0, |bytes| Self::read_whole(internals, bytes))
+        }
+    }
+
+    /// Read until the end of the stream, appending to `buf`.
+    #[inline]
+    pub(crate) fn read_str_to_end<Inner: Read>(
+        internals: &mut impl Utf8ReaderInternals<Inner>,
+        buf: &mut String,
+    ) -> io::Result<usize> {
+        let chunk = max(DEFAULT_BUF_SIZE, internals.impl_().min_buf_len());
+
+        // Safety: Our `read` implementation only produces complete UTF-8
+        // encodings.
+        unsafe { read_to_end_with(buf, chunk, |bytes| Self::read_whole(internals, bytes)) }
+    }
+
+    /// Read until the end of the stream, appending to `buf` only if nothing
+    /// fails, so that `buf` is left unchanged on an error, as
+    /// `Read::read_to_string` requires.
     #[inline]
     pub(crate) fn read_to_string<Inner: Read>(
         internals: &mut impl Utf8ReaderInternals<Inner>,
         buf: &mut String,
     ) -> io::Result<usize> {
-        // Safety: Our `read` implementation already ensures that its output
-        // is UTF-8, so we can just unwrap it here.
-        internals.read_to_end(unsafe { buf.as_mut_vec() })
+        let mut s = String::new();
+        let len = Self::read_str_to_end(internals, &mut s)?;
+        buf.push_str(&s);
+        Ok(len)
     }
 }
 
-/// What to do when there is an incomplete UTF-8 sequence at the end of
-/// the overflow buffer.
-enum IncompleteHow {
-    /// Include the incomplete sequence in the output.
-    Include,
-    /// Leave the incomplete sequence in the overflow buffer.
-    Exclude,
-    /// Replace the incomplete sequence with U+FFFD.
-    Replace,
+/// Return how many bytes of `bytes`, the input at `pos` in the input stream,
+/// to skip to reach the next scalar value boundary of input in `encoding`.
+fn char_boundary_skip(encoding: Encoding, pos: u64, bytes: &[u8]) -> usize {
+    let skip = match encoding {
+        // Skip continuation bytes.
+        Encoding::Utf8 => bytes
+            .iter()
+            .take_while(|byte| **byte & 0xc0 == 0x80)
+            .count(),
+        // Skip to the next code unit, and past it if it's a low surrogate.
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let align = (pos % 2) as usize;
+            let unit = bytes.get(align..align + 2).map(|unit| {
+                let unit = [unit[0], unit[1]];
+                match encoding {
+                    Encoding::Utf16Le => u16::from_le_bytes(unit),
+                    _ => u16::from_be_bytes(unit),
+                }
+            });
+            match unit {
+                Some(0xdc00..=0xdfff) => align + 2,
+                _ => align,
+            }
+        }
+        // Skip to the next code unit.
+        Encoding::Utf32Le | Encoding::Utf32Be => ((4 - pos % 4) % 4) as usize,
+    };
+    min(skip, bytes.len())
 }
@@ -1,9 +1,13 @@
-use crate::{default_write_str, Utf8Duplexer, Utf8Writer};
+use crate::invalid::{invalid_output, unescape_char};
+use crate::{
+    default_write_str, default_write_str_vectored, Utf8Duplexer, Utf8Error, Utf8Validator,
+    Utf8Writer,
+};
 use duplex::Duplex;
 #[cfg(feature = "layered-io")]
 use layered_io::{HalfDuplexLayered, WriteLayered};
 use std::cmp::min;
-use std::io::{self, Read, Write};
+use std::io::{self, IoSlice, Read, Write};
 use std::str;
 
 pub(crate) trait Utf8WriterInternals<Inner: Write>: Write {
@@ -12,7 +16,6 @@ pub(crate) trait Utf8WriterInternals<Inner: Write>: Write {
     fn inner(&self) -> &Inner;
     fn inner_mut(&mut self) -> &mut Inner;
     fn into_inner(self) -> Inner;
-    fn write_incomplete(&mut self, utf8_len: usize) -> io::Result<()>;
 }
 
 #[cfg(feature = "layered-io")]
@@ -38,13 +41,6 @@ impl<Inner: Write> Utf8WriterInternals<Inner> for Utf8Writer<Inner> {
     fn into_inner(self) -> Inner {
         self.inner
     }
-
-    fn write_incomplete(&mut self, utf8_len: usize) -> io::Result<()> {
-        let to_write = &self.output.incomplete[..utf8_len];
-        self.output.incomplete_len = 0;
-        self.inner.write_all(to_write)?;
-        Ok(())
-    }
 }
 
 #[cfg(feature = "layered-io")]
@@ -67,21 +63,30 @@ impl<Inner: Duplex + Read + Write> Utf8WriterInternals<Inner> for Utf8Duplexer<I
     fn into_inner(self) -> Inner {
         self.inner
     }
-
-    fn write_incomplete(&mut self, utf8_len: usize) -> io::Result<()> {
-        let to_write = &self.output.incomplete[..utf8_len];
-        self.output.incomplete_len = 0;
-        self.inner.write_all(to_write)?;
-        Ok(())
-    }
 }
 
 #[cfg(feature = "layered-io")]
 impl<Inner: HalfDuplexLayered> Utf8WriterInternalsLayered<Inner> for Utf8Duplexer<Inner> {}
 
 pub(crate) struct Utf8Output {
-    incomplete: [u8; 4],
-    incomplete_len: u8,
+    /// Validation state for bytes written with `write`, holding the start of
+    /// a scalar value's encoding which hasn't been completed yet.
+    validator: Utf8Validator,
+
+    /// The rest of a scalar value's encoding which has been accepted by
+    /// `write_str_partial`, but which the inner stream hasn't accepted yet.
+    pending: [u8; 4],
+    pending_pos: u8,
+    pending_len: u8,
+
+    /// The number of bytes written so far, not counting any incomplete
+    /// encoding held by `validator`, which is the offset of any incomplete or
+    /// invalid encoding which follows.
+    written: u64,
+
+    /// Whether to translate escapes produced by `InvalidPolicy::Escape` back
+    /// into the bytes they represent.
+    unescape: bool,
 }
 
 impl Utf8Output {
@@ -89,11 +94,21 @@ impl Utf8Output {
     #[inline]
     pub(crate) const fn new() -> Self {
         Self {
-            incomplete: [0, 0, 0, 0],
-            incomplete_len: 0,
+            validator: Utf8Validator::new(),
+            pending: [0, 0, 0, 0],
+            pending_pos: 0,
+            pending_len: 0,
+            written: 0,
+            unescape: false,
         }
     }
 
+    /// Set whether to translate escapes back into the bytes they represent.
+    #[inline]
+    pub(crate) fn set_unescape(&mut self, unescape: bool) {
+        self.unescape = unescape;
+    }
+
     /// Flush and close the underlying stream and return the underlying
     /// stream object.
     #[inline]
@@ -118,12 +133,15 @@ impl Utf8Output {
     pub(crate) fn close<Inner: WriteLayered>(
         internals: &mut impl Utf8WriterInternalsLayered<Inner>,
     ) -> io::Result<()> {
+        Self::write_pending(internals)?;
         internals.inner_mut().close()
     }
 
     #[cfg(feature = "layered-io")]
     #[inline]
     pub(crate) fn abandon<Inner: WriteLayered>(internals: &mut impl Utf8WriterInternals<Inner>) {
+        internals.impl_().pending_pos = 0;
+        internals.impl_().pending_len = 0;
         internals.inner_mut().abandon()
     }
 
@@ -140,7 +158,215 @@ impl Utf8Output {
         internals: &mut impl Utf8WriterInternals<Inner>,
         s: &str,
     ) -> io::Result<()> {
-        default_write_str(internals.inner_mut(), s)
+        if !internals.impl_().validator.incomplete().is_empty() {
+            return Err(internals.impl_().write_str_error());
+        }
+        Self::write_pending(internals)?;
+        if internals.impl_().unescape {
+            Self::write_unescaped(internals.inner_mut(), s)?;
+        } else {
+            default_write_str(internals.inner_mut(), s)?;
+        }
+        internals.impl_().written += s.len() as u64;
+        Ok(())
+    }
+
+    /// Write a prefix of `s`, and return its length, which is always at a
+    /// `char` boundary. If the inner stream accepts part of a scalar value's
+    /// encoding, hold on to the rest of it in `pending`, to be written before
+    /// any further output.
+    pub(crate) fn write_str_partial<Inner: Write>(
+        internals: &mut impl Utf8WriterInternals<Inner>,
+        s: &str,
+    ) -> io::Result<usize> {
+        if !internals.impl_().validator.incomplete().is_empty() {
+            return Err(internals.impl_().write_str_error());
+        }
+        Self::write_pending(internals)?;
+        if s.is_empty() {
+            return Ok(0);
+        }
+
+        let n = if internals.impl_().unescape {
+            Self::write_unescaped_partial(internals.inner_mut(), s)?
+        } else {
+            internals.inner_mut().write(s.as_bytes())?
+        };
+        let mut end = n;
+        while !s.is_char_boundary(end) {
+            end += 1;
+        }
+
+        let impl_ = internals.impl_();
+        if end != n {
+            impl_.pending[..end - n].copy_from_slice(&s.as_bytes()[n..end]);
+            impl_.pending_pos = 0;
+            impl_.pending_len = (end - n) as u8;
+        }
+        impl_.written += end as u64;
+        Ok(end)
+    }
+
+    /// Write all of `bufs`, with vectored writes to the inner stream.
+    pub(crate) fn write_str_vectored<Inner: Write>(
+        internals: &mut impl Utf8WriterInternals<Inner>,
+        bufs: &[&str],
+    ) -> io::Result<()> {
+        if !internals.impl_().validator.incomplete().is_empty() {
+            return Err(internals.impl_().write_str_error());
+        }
+        if internals.impl_().unescape {
+            return bufs.iter().try_for_each(|s| Self::write_str(internals, s));
+        }
+        Self::write_pending(internals)?;
+        default_write_str_vectored(internals.inner_mut(), bufs)?;
+        internals.impl_().written += bufs.iter().map(|s| s.len() as u64).sum::<u64>();
+        Ok(())
+    }
+
+    /// Write a prefix of the concatenation of `bufs`, validating it as a
+    /// whole, with a single vectored write to the inner stream.
+    pub(crate) fn write_vectored<Inner: Write>(
+        internals: &mut impl Utf8WriterInternals<Inner>,
+        bufs: &[IoSlice<'_>],
+    ) -> io::Result<usize> {
+        // Incomplete, invalid, and escaped input is handled by `write`.
+        let valid_len = valid_prefix_len(bufs);
+        if !internals.impl_().validator.incomplete().is_empty()
+            || internals.impl_().unescape
+            || valid_len == 0
+        {
+            return match bufs.iter().find(|buf| !buf.is_empty()) {
+                Some(buf) => Self::write(internals, buf),
+                None => Ok(0),
+            };
+        }
+        Self::write_pending(internals)?;
+
+        // Pass the valid prefix to the inner stream.
+        let n = if valid_len == bufs.iter().map(|buf| buf.len()).sum() {
+            internals.inner_mut().write_vectored(bufs)?
+        } else {
+            let mut remaining = valid_len;
+            let valid = bufs
+                .iter()
+                .map_while(|buf| {
+                    let len = min(buf.len(), remaining);
+                    remaining -= len;
+                    (len != 0).then(|| IoSlice::new(&buf[..len]))
+                })
+                .collect::<Vec<_>>();
+            internals.inner_mut().write_vectored(&valid)?
+        };
+
+        // If the inner stream accepted part of a scalar value's encoding,
+        // hold on to the rest of it.
+        let impl_ = internals.impl_();
+        let rest = bufs
+            .iter()
+            .flat_map(|buf| buf.iter())
+            .take(valid_len)
+            .skip(n)
+            .take_while(|byte| **byte & 0xc0 == 0x80)
+            .take(3);
+        let mut pending_len = 0;
+        for byte in rest {
+            impl_.pending[pending_len] = *byte;
+            pending_len += 1;
+        }
+        impl_.pending_pos = 0;
+        impl_.pending_len = pending_len as u8;
+        impl_.written += (n + pending_len) as u64;
+        Ok(n + pending_len)
+    }
+
+    /// Write the rest of any scalar value's encoding held in `pending`.
+    fn write_pending<Inner: Write>(
+        internals: &mut impl Utf8WriterInternals<Inner>,
+    ) -> io::Result<()> {
+        loop {
+            let impl_ = internals.impl_();
+            let (pos, len) = (
+                usize::from(impl_.pending_pos),
+                usize::from(impl_.pending_len),
+            );
+            if pos == len {
+                return Ok(());
+            }
+            let pending = impl_.pending;
+            match internals.inner_mut().write(&pending[pos..len]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole scalar value",
+                    ))
+                }
+                Ok(n) => internals.impl_().pending_pos += n as u8,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Check that `bytes`, a completed encoding held by `validator`, is
+    /// valid.
+    fn check_incomplete<'a>(&self, bytes: &'a [u8]) -> io::Result<&'a str> {
+        str::from_utf8(bytes)
+            .map_err(|error| invalid_output(self.written, &bytes[..error.error_len().unwrap()]))
+    }
+
+    /// Return the error for an incomplete encoding which is still pending.
+    #[cold]
+    fn incomplete_error(&self) -> io::Error {
+        Utf8Error::IncompleteAtFlush {
+            offset: self.written,
+            bytes: self.validator.incomplete().to_vec(),
+        }
+        .into()
+    }
+
+    /// Return the error for a `write_str` while an incomplete encoding is
+    /// pending.
+    #[cold]
+    fn write_str_error(&self) -> io::Error {
+        Utf8Error::IncompleteAtWriteStr {
+            offset: self.written,
+            bytes: self.validator.incomplete().to_vec(),
+        }
+        .into()
+    }
+
+    /// Write `s`, translating any escapes produced by `InvalidPolicy::Escape`
+    /// back into the bytes they represent.
+    #[cold]
+    fn write_unescaped<Inner: Write>(inner: &mut Inner, s: &str) -> io::Result<()> {
+        let mut start = 0;
+        for (index, c) in s.char_indices() {
+            if let Some(byte) = unescape_char(c) {
+                default_write_str(inner, &s[start..index])?;
+                inner.write_all(&[byte])?;
+                start = index + c.len_utf8();
+            }
+        }
+        default_write_str(inner, &s[start..])
+    }
+
+    /// Write a prefix of `s`, translating escapes back into the bytes they
+    /// represent, and return the length of the prefix, which is at a `char`
+    /// boundary if it ends with an escape.
+    #[cold]
+    fn write_unescaped_partial<Inner: Write>(inner: &mut Inner, s: &str) -> io::Result<usize> {
+        let escape = s
+            .char_indices()
+            .find_map(|(index, c)| unescape_char(c).map(|byte| (index, c, byte)));
+        match escape {
+            Some((0, c, byte)) => match inner.write(&[byte])? {
+                0 => Ok(0),
+                _ => Ok(c.len_utf8()),
+            },
+            Some((index, _, _)) => inner.write(&s.as_bytes()[..index]),
+            None => inner.write(s.as_bytes()),
+        }
     }
 
     pub(crate) fn write<Inner: Write>(
@@ -151,34 +377,20 @@ impl Utf8Output {
 
         // If we have incomplete bytes from the previous `write`, try to
         // complete them.
-        let incomplete_len = usize::from(internals.impl_().incomplete_len);
         let mut buf_len = buf.len();
-        if incomplete_len != 0 {
-            // Compute how any bytes we need for the UTF-8 encoding.
-            let utf8_len = match internals.impl_().incomplete[0] & 0x30 {
-                0x20 => 3,
-                0x30 => 4,
-                _ => 2,
-            };
-
-            // We're only given so many bytes.
-            let copy_len = min(utf8_len - incomplete_len, buf_len);
-
-            // Copy `copy_len` bytes from `buf` into the `incomplete` buffer.
-            internals.impl_().incomplete[incomplete_len..(incomplete_len + copy_len)]
-                .copy_from_slice(&buf[..copy_len]);
+        if !internals.impl_().validator.incomplete().is_empty() {
+            let (copy_len, completed) = internals.impl_().validator.complete(buf);
             written += copy_len;
 
-            let new_incomplete_len = incomplete_len + copy_len;
-            internals.impl_().incomplete_len = new_incomplete_len as u8;
-
             // If the sequence is still incomplete, wait for the next `write`.
-            if new_incomplete_len < utf8_len {
-                return Ok(written);
-            }
+            let (bytes, utf8_len) = match completed {
+                Some(completed) => completed,
+                None => return Ok(written),
+            };
 
             // The sequence is complete; write it.
-            internals.write_incomplete(utf8_len)?;
+            let s = internals.impl_().check_incomplete(&bytes[..utf8_len])?;
+            Self::write_str(internals, s)?;
             buf = &buf[copy_len..];
             buf_len = buf.len();
         }
@@ -191,19 +403,16 @@ impl Utf8Output {
             Err(error) => {
                 let valid_up_to = error.valid_up_to();
                 if valid_up_to != 0 {
-                    internals
-                        .inner_mut()
-                        .write_all(&buf[..valid_up_to])
-                        .map(|()| valid_up_to)?;
+                    // Safety: `from_utf8` validated this part of `buf`.
+                    let valid = unsafe { str::from_utf8_unchecked(&buf[..valid_up_to]) };
+                    Self::write_str(internals, valid)?;
                 }
                 if error.error_len().is_none() {
-                    let incomplete_len = buf_len - valid_up_to;
-                    internals.impl_().incomplete[..incomplete_len]
-                        .copy_from_slice(&buf[valid_up_to..]);
-                    internals.impl_().incomplete_len = incomplete_len as u8;
+                    internals.impl_().validator.hold(&buf[valid_up_to..]);
                     Ok(written + buf_len)
                 } else {
-                    Err(io::Error::new(io::ErrorKind::InvalidData, error))
+                    let invalid = &buf[valid_up_to..][..error.error_len().unwrap()];
+                    Err(invalid_output(internals.impl_().written, invalid))
                 }
             }
         }
@@ -213,23 +422,63 @@ impl Utf8Output {
     pub(crate) fn flush<Inner: Write>(
         internals: &mut impl Utf8WriterInternals<Inner>,
     ) -> io::Result<()> {
-        if internals.impl_().incomplete_len != 0 {
-            internals.impl_().incomplete_len = 0;
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "incomplete UTF-8 encoding at flush",
-            ));
+        let impl_ = internals.impl_();
+        if !impl_.validator.incomplete().is_empty() {
+            let error = impl_.incomplete_error();
+            impl_.validator.take_incomplete();
+            return Err(error);
         }
+        Self::write_pending(internals)?;
         internals.inner_mut().flush()
     }
 }
 
+/// Return the length of the longest prefix of the concatenation of `bufs`
+/// which is valid UTF-8. Encodings may straddle buffers.
+fn valid_prefix_len(bufs: &[IoSlice<'_>]) -> usize {
+    let mut valid_len = 0;
+    let mut validator = Utf8Validator::new();
+    for buf in bufs {
+        let mut buf = &buf[..];
+
+        // Complete an encoding which straddles the previous buffer.
+        if !validator.incomplete().is_empty() {
+            let (copy_len, completed) = validator.complete(buf);
+            buf = &buf[copy_len..];
+            let (bytes, utf8_len) = match completed {
+                Some(completed) => completed,
+                None => continue,
+            };
+            if str::from_utf8(&bytes[..utf8_len]).is_err() {
+                return valid_len;
+            }
+            valid_len += utf8_len;
+        }
+
+        match str::from_utf8(buf) {
+            Ok(_) => valid_len += buf.len(),
+            Err(error) => {
+                valid_len += error.valid_up_to();
+                if error.error_len().is_some() {
+                    return valid_len;
+                }
+                validator.hold(&buf[error.valid_up_to()..]);
+            }
+        }
+    }
+    valid_len
+}
+
 impl Drop for Utf8Output {
     fn drop(&mut self) {
-        if self.incomplete_len == 0 {
+        if self.validator.incomplete().is_empty() {
             // oll korrect
         } else {
-            panic!("output text stream not ended on UTF-8 boundary");
+            let error = Utf8Error::IncompleteAtDrop {
+                offset: self.written,
+                bytes: self.validator.incomplete().to_vec(),
+            };
+            panic!("{}", error);
         }
     }
 }
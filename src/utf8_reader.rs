@@ -1,10 +1,10 @@
 use crate::utf8_input::Utf8Input;
-use crate::ReadStr;
+use crate::{Encoding, Granularity, InvalidPolicy, InvalidSequence, ReadStr, Replacement};
 #[cfg(windows)]
 use io_extras::os::windows::{
     AsHandleOrSocket, AsRawHandleOrSocket, BorrowedHandleOrSocket, RawHandleOrSocket,
 };
-use std::io::{self, Read};
+use std::io::{self, IoSliceMut, Read, Seek, SeekFrom};
 use std::{fmt, str};
 #[cfg(feature = "terminal-io")]
 use terminal_io::{ReadTerminal, Terminal};
@@ -24,7 +24,18 @@ use {
 /// sequences replaced by [U+FFFD (REPLACEMENT CHARACTER)] in the manner of
 /// [`String::from_utf8_lossy`], where scalar value encodings never straddle
 /// `read` calls (callers can do [`str::from_utf8`] and it will always
-/// succeed).
+/// succeed). The exception is a `read` into a buffer too short to hold the
+/// next scalar value, which gets as much of its encoding as fits, with the
+/// rest following in subsequent reads.
+///
+/// Invalid sequences may instead be reported as errors, skipped, escaped, or
+/// decoded as Windows-1252 or Latin-1, by constructing the reader with
+/// [`Utf8Reader::with_policy`].
+///
+/// Non-blocking inner streams are supported. Errors from the inner stream,
+/// including [`io::ErrorKind::WouldBlock`], are returned as they are, and the
+/// start of a scalar value's encoding which has already been read is held,
+/// through any number of errors, until later input completes it.
 ///
 /// [U+FFFD (REPLACEMENT CHARACTER)]: https://util.unicode.org/UnicodeJsps/character.jsp?a=FFFD
 pub struct Utf8Reader<Inner: Read> {
@@ -39,11 +50,115 @@ impl<Inner: Read> Utf8Reader<Inner> {
     /// Construct a new instance of `Utf8Reader` wrapping `inner`.
     #[inline]
     pub fn new(inner: Inner) -> Self {
+        Self::with_policy(inner, InvalidPolicy::Replace)
+    }
+
+    /// Construct a new instance of `Utf8Reader` wrapping `inner`, which
+    /// handles invalid sequences according to `policy`.
+    #[inline]
+    pub fn with_policy(inner: Inner, policy: InvalidPolicy) -> Self {
         Self {
             inner,
-            input: Utf8Input::new(),
+            input: Utf8Input::new(policy),
         }
     }
+
+    /// Set how many replacements are produced for each invalid sequence.
+    /// The default is [`Granularity::MaximalSubpart`].
+    #[inline]
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.input.decoder.set_granularity(granularity);
+        self
+    }
+
+    /// Set the text which invalid sequences are replaced with when using
+    /// [`InvalidPolicy::Replace`]. The default is U+FFFD.
+    ///
+    /// Reads with a buffer shorter than the replacement, or than 4 bytes,
+    /// are supported by reading ahead, but are less efficient.
+    #[inline]
+    pub fn replacement(mut self, replacement: impl Into<Replacement>) -> Self {
+        self.input.decoder.set_replacement(replacement.into());
+        self
+    }
+
+    /// Set whether to strip a leading UTF-8 byte order mark (U+FEFF) from
+    /// the input. By default it's passed through.
+    #[inline]
+    pub fn strip_bom(mut self, strip_bom: bool) -> Self {
+        self.input.decoder.set_strip_bom(strip_bom);
+        self
+    }
+
+    /// When stripping a byte order mark or detecting the encoding, return
+    /// whether a byte order mark was present, or `None` if not enough input
+    /// has been read to tell yet, or if neither is enabled.
+    #[inline]
+    pub fn had_bom(&self) -> Option<bool> {
+        self.input.decoder.had_bom()
+    }
+
+    /// Set whether to detect the encoding of the input from a leading byte
+    /// order mark, transcoding UTF-16 and UTF-32 input into UTF-8. Input
+    /// without a byte order mark is read as UTF-8. The byte order mark is
+    /// stripped. By default, input is always read as UTF-8.
+    ///
+    /// Unpaired surrogates and other invalid sequences in UTF-16 and UTF-32
+    /// input are handled according to the reader's [`InvalidPolicy`], except
+    /// that policies which work a byte at a time replace them, as with
+    /// [`InvalidPolicy::Replace`].
+    #[inline]
+    pub fn detect_encoding(mut self, detect_encoding: bool) -> Self {
+        self.input.decoder.set_detect_encoding(detect_encoding);
+        self
+    }
+
+    /// Return the encoding of the input, or `None` if the encoding is being
+    /// detected and not enough input has been read to tell yet.
+    #[inline]
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.input.decoder.encoding()
+    }
+
+    /// Set whether to accept [CESU-8] surrogate pairs, encoding
+    /// supplementary characters as two three-byte sequences, and the
+    /// overlong encoding of U+0000 as C0 80 used by Java's [Modified UTF-8].
+    /// Unpaired surrogates and other invalid sequences are still handled
+    /// according to the reader's [`InvalidPolicy`]. By default, these are
+    /// invalid.
+    ///
+    /// [CESU-8]: https://www.unicode.org/reports/tr26/
+    /// [Modified UTF-8]: https://docs.oracle.com/javase/8/docs/api/java/io/DataInput.html#modified-utf-8
+    #[inline]
+    pub fn modified_utf8(mut self, modified_utf8: bool) -> Self {
+        self.input.decoder.set_modified_utf8(modified_utf8);
+        self
+    }
+
+    /// Register a callback which is called with each invalid sequence
+    /// encountered in the input, before it is handled according to the
+    /// reader's [`InvalidPolicy`].
+    #[inline]
+    pub fn on_invalid(
+        mut self,
+        on_invalid: impl FnMut(&InvalidSequence) + Send + Sync + 'static,
+    ) -> Self {
+        self.input.decoder.set_on_invalid(on_invalid);
+        self
+    }
+
+    /// Set whether seeking skips forward past up to 3 continuation bytes at
+    /// the new position, so that reading starts at the next scalar value
+    /// boundary of UTF-8 input rather than with replacements for the tail of
+    /// a scalar value. UTF-16 and UTF-32 input, selected by
+    /// [`Self::detect_encoding`], is skipped forward to the next code unit,
+    /// and past a low surrogate. By default, seeking goes to exactly the
+    /// requested position.
+    #[inline]
+    pub fn seek_to_char_boundary(mut self, seek_to_char_boundary: bool) -> Self {
+        self.input.set_seek_to_char_boundary(seek_to_char_boundary);
+        self
+    }
 }
 
 #[cfg(feature = "terminal-io")]
@@ -98,6 +213,26 @@ impl<Inner: Read> ReadStr for Utf8Reader<Inner> {
     fn read_exact_str(&mut self, buf: &mut str) -> io::Result<()> {
         Utf8Input::read_exact_str(self, buf)
     }
+
+    #[inline]
+    fn read_str_append(&mut self, buf: &mut String, max: usize) -> io::Result<usize> {
+        Utf8Input::read_str_append(self, buf, max)
+    }
+
+    #[inline]
+    fn read_str_to_end(&mut self, buf: &mut String) -> io::Result<usize> {
+        Utf8Input::read_str_to_end(self, buf)
+    }
+
+    #[inline]
+    fn read_char(&mut self) -> io::Result<Option<char>> {
+        Utf8Input::read_char(self)
+    }
+
+    #[inline]
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        Utf8Input::peek_char(self)
+    }
 }
 
 #[cfg(feature = "layered-io")]
@@ -119,12 +254,29 @@ impl<Inner: Read> Read for Utf8Reader<Inner> {
         Utf8Input::read(self, buf)
     }
 
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        Utf8Input::read_vectored(self, bufs)
+    }
+
     #[inline]
     fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
         Utf8Input::read_to_string(self, buf)
     }
 }
 
+/// Seeking discards any input which has been read from the inner stream but
+/// not returned yet. Positions are in the input stream, and seeking relative
+/// to the current position is relative to the input of the next output to
+/// be returned, including output which has been read ahead, such as by
+/// [`ReadStr::peek_char`].
+impl<Inner: Read + Seek> Seek for Utf8Reader<Inner> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        Utf8Input::seek(self, pos)
+    }
+}
+
 #[cfg(not(windows))]
 impl<Inner: Read + AsRawFd> AsRawFd for Utf8Reader<Inner> {
     #[inline]
@@ -166,24 +318,25 @@ impl<Inner: Read + fmt::Debug> fmt::Debug for Utf8Reader<Inner> {
 }
 
 #[cfg(test)]
-fn translate_via_reader(bytes: &[u8]) -> String {
-    let mut reader = Utf8Reader::new(bytes);
+fn translate_via_reader(bytes: &[u8], granularity: Granularity) -> String {
+    let mut reader = Utf8Reader::new(bytes).granularity(granularity);
     let mut s = String::new();
     reader.read_to_string(&mut s).unwrap();
     s
 }
 
 #[cfg(test)]
-fn translate_via_layered_reader(bytes: &[u8]) -> String {
-    let mut reader = Utf8Reader::new(layered_io::LayeredReader::new(bytes));
+fn translate_via_layered_reader(bytes: &[u8], granularity: Granularity) -> String {
+    let mut reader =
+        Utf8Reader::new(layered_io::LayeredReader::new(bytes)).granularity(granularity);
     let mut s = String::new();
     reader.read_to_string(&mut s).unwrap();
     s
 }
 
 #[cfg(test)]
-fn translate_via_slice_reader(bytes: &[u8]) -> String {
-    let mut reader = Utf8Reader::new(layered_io::SliceReader::new(bytes));
+fn translate_via_slice_reader(bytes: &[u8], granularity: Granularity) -> String {
+    let mut reader = Utf8Reader::new(layered_io::SliceReader::new(bytes)).granularity(granularity);
     let mut s = String::new();
     reader.read_to_string(&mut s).unwrap();
     s
@@ -191,8 +344,8 @@ fn translate_via_slice_reader(bytes: &[u8]) -> String {
 
 #[cfg(test)]
 #[cfg(feature = "layered-io")]
-fn translate_with_small_buffer(bytes: &[u8]) -> String {
-    let mut reader = Utf8Reader::new(layered_io::SliceReader::new(bytes));
+fn translate_with_small_buffer(bytes: &[u8], granularity: Granularity) -> String {
+    let mut reader = Utf8Reader::new(layered_io::SliceReader::new(bytes)).granularity(granularity);
     let mut v = Vec::new();
     let mut buf = [0; 4];
     loop {
@@ -207,8 +360,9 @@ fn translate_with_small_buffer(bytes: &[u8]) -> String {
 
 #[cfg(test)]
 #[cfg(not(feature = "layered-io"))]
-fn translate_with_small_buffer(bytes: &[u8]) -> String {
-    let mut reader = Utf8Reader::new(bytes);
+#[allow(clippy::unnecessary_literal_unwrap)]
+fn translate_with_small_buffer(bytes: &[u8], granularity: Granularity) -> String {
+    let mut reader = Utf8Reader::new(bytes).granularity(granularity);
     let mut v = Vec::new();
     let mut buf = [0; 4];
     loop {
@@ -224,34 +378,56 @@ fn translate_with_small_buffer(bytes: &[u8]) -> String {
 }
 
 #[cfg(test)]
-fn test(bytes: &[u8], s: &str) {
-    assert_eq!(translate_via_reader(bytes), s);
-    assert_eq!(translate_via_layered_reader(bytes), s);
-    assert_eq!(translate_via_slice_reader(bytes), s);
-    assert_eq!(translate_with_small_buffer(bytes), s);
+fn test_with_granularity(bytes: &[u8], granularity: Granularity, s: &str) {
+    assert_eq!(translate_via_reader(bytes, granularity), s);
+    assert_eq!(translate_via_layered_reader(bytes, granularity), s);
+    assert_eq!(translate_via_slice_reader(bytes, granularity), s);
+    assert_eq!(translate_with_small_buffer(bytes, granularity), s);
 
     for i in 1..4 {
         let mut v = vec![0_u8; i + bytes.len()];
         v[i..i + bytes.len()].copy_from_slice(bytes);
         assert_eq!(
-            str::from_utf8(&translate_via_reader(&v).as_bytes()[i..]).unwrap(),
+            str::from_utf8(&translate_via_reader(&v, granularity).as_bytes()[i..]).unwrap(),
             s
         );
         assert_eq!(
-            str::from_utf8(&translate_via_layered_reader(&v).as_bytes()[i..]).unwrap(),
+            str::from_utf8(&translate_via_layered_reader(&v, granularity).as_bytes()[i..]).unwrap(),
             s
         );
         assert_eq!(
-            str::from_utf8(&translate_via_slice_reader(&v).as_bytes()[i..]).unwrap(),
+            str::from_utf8(&translate_via_slice_reader(&v, granularity).as_bytes()[i..]).unwrap(),
             s
         );
         assert_eq!(
-            str::from_utf8(&translate_with_small_buffer(&v).as_bytes()[i..]).unwrap(),
+            str::from_utf8(&translate_with_small_buffer(&v, granularity).as_bytes()[i..]).unwrap(),
             s
         );
     }
 }
 
+/// Test translating `bytes` with each granularity, where `maximal_subpart`
+/// and `per_byte` are the expected results.
+#[cfg(test)]
+fn test_granularities(bytes: &[u8], maximal_subpart: &str, per_byte: &str) {
+    test_with_granularity(bytes, Granularity::MaximalSubpart, maximal_subpart);
+    test_with_granularity(bytes, Granularity::PerByte, per_byte);
+
+    // With one replacement per byte, invalid bytes map one-to-one onto
+    // replacements.
+    assert_eq!(
+        per_byte.chars().filter(|c| *c == '\u{fffd}').count(),
+        bytes.len() - per_byte.replace('\u{fffd}', "").len()
+    );
+}
+
+/// Test translating `bytes`, where the expected result `s` is the same with
+/// each granularity.
+#[cfg(test)]
+fn test(bytes: &[u8], s: &str) {
+    test_granularities(bytes, s, s);
+}
+
 #[test]
 fn test_empty_string() {
     test(b"", "");
@@ -519,7 +695,7 @@ fn test_three_byte_lead() {
 }
 #[test]
 fn test_three_byte_lead_and_one_trail() {
-    test(b"\xE2\x98", "�");
+    test_granularities(b"\xE2\x98", "�", "��");
 }
 #[test]
 fn test_four_byte_lead() {
@@ -527,11 +703,11 @@ fn test_four_byte_lead() {
 }
 #[test]
 fn test_four_byte_lead_and_one_trail() {
-    test(b"\xF0\x9F", "�");
+    test_granularities(b"\xF0\x9F", "�", "��");
 }
 #[test]
 fn test_four_byte_lead_and_two_trails() {
-    test(b"\xF0\x9F\x92", "�");
+    test_granularities(b"\xF0\x9F\x92", "�", "���");
 }
 
 // Leftovers
@@ -553,3 +729,1075 @@ fn test_ff() {
 fn test_ff_and_trail() {
     test(b"\xFF\x80", "��");
 }
+
+// Truncated sequences followed by more input
+#[test]
+fn test_three_byte_lead_and_one_trail_mid_stream() {
+    test_granularities(b"\xE2\x98a", "�a", "��a");
+}
+#[test]
+fn test_four_byte_lead_and_two_trails_mid_stream() {
+    test_granularities(b"\xF0\x9F\x92a", "�a", "���a");
+}
+#[test]
+fn test_truncated_then_valid_four_byte() {
+    test_granularities(b"\xF0\x9F\xF0\x9F\x92\xA9", "�💩", "��💩");
+}
+
+/// Read all of `reader` with `read_str`, `buf_len` bytes at a time. Errors
+/// other than `Interrupted` are passed to `on_error`, which may append to the
+/// output and continue reading, or fail.
+#[cfg(test)]
+fn read_all<R: Read>(
+    reader: &mut Utf8Reader<R>,
+    buf_len: usize,
+    mut on_error: impl FnMut(io::Error, &mut String) -> io::Result<()>,
+) -> io::Result<String> {
+    let mut s = String::new();
+    let mut buf = "\0".repeat(buf_len);
+    loop {
+        match reader.read_str(&mut buf) {
+            Ok(0) => break,
+            Ok(size) => s.push_str(&buf[..size]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => on_error(err, &mut s)?,
+        }
+    }
+    Ok(s)
+}
+
+// Invalid sequence policies
+
+/// Translate `bytes`, rendering any reported invalid sequences inline as
+/// `<xx xx>`.
+#[cfg(test)]
+fn translate_with_policy(bytes: &[u8], policy: InvalidPolicy, buf_len: usize) -> String {
+    use crate::Utf8Error;
+
+    let mut reader = Utf8Reader::with_policy(layered_io::SliceReader::new(bytes), policy);
+    read_all(&mut reader, buf_len, |err, s| {
+        let invalid = match err.get_ref().and_then(|e| e.downcast_ref::<Utf8Error>()) {
+            Some(Utf8Error::InvalidSequence(invalid)) => invalid,
+            _ => return Err(err),
+        };
+        let hex: Vec<String> = invalid
+            .bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        s.push_str(&format!("<{}>", hex.join(" ")));
+        Ok(())
+    })
+    .unwrap()
+}
+
+#[cfg(test)]
+fn test_policy(bytes: &[u8], policy: InvalidPolicy, s: &str) {
+    for buf_len in [4, 5, 8, 64] {
+        assert_eq!(translate_with_policy(bytes, policy, buf_len), s);
+    }
+}
+
+#[test]
+fn test_replace_policy() {
+    test_policy(b"hello\xffworld", InvalidPolicy::Replace, "hello�world");
+    test_policy(b"\xE2\x98\x83\x80", InvalidPolicy::Replace, "☃�");
+    test_policy(b"hello\xE2\x98", InvalidPolicy::Replace, "hello�");
+}
+
+#[test]
+fn test_error_policy() {
+    test_policy(b"hello world", InvalidPolicy::Error, "hello world");
+    test_policy(b"hello\xffworld", InvalidPolicy::Error, "hello<ff>world");
+    test_policy(b"\xff\xff", InvalidPolicy::Error, "<ff><ff>");
+    test_policy(b"\xC0\x80", InvalidPolicy::Error, "<c0><80>");
+    test_policy(b"\xF0\x9F\x92\xA9\x80", InvalidPolicy::Error, "💩<80>");
+    test_policy(
+        b"\xE2\x98\x83\xED\xA0\x80",
+        InvalidPolicy::Error,
+        "☃<ed><a0><80>",
+    );
+    test_policy(b"hello\xE2\x98", InvalidPolicy::Error, "hello<e2 98>");
+    test_policy(b"\xF0\x9F\x92", InvalidPolicy::Error, "<f0 9f 92>");
+}
+
+#[test]
+fn test_error_policy_returns_valid_data_first() {
+    let mut reader = Utf8Reader::with_policy(&b"hello\xffworld"[..], InvalidPolicy::Error);
+    let mut buf = "\0".repeat(64);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], "hello");
+    assert_eq!(
+        reader.read_str(&mut buf).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], "world");
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn test_error_policy_read_to_string() {
+    let mut reader = Utf8Reader::with_policy(&b"hello\xffworld"[..], InvalidPolicy::Error);
+    let mut s = String::from("prefix:");
+    assert_eq!(
+        reader.read_to_string(&mut s).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(s, "prefix:");
+    assert_eq!(reader.read_to_string(&mut s).unwrap(), 5);
+    assert_eq!(s, "prefix:world");
+}
+
+#[test]
+fn test_skip_policy() {
+    test_policy(b"hello world", InvalidPolicy::Skip, "hello world");
+    test_policy(b"hello\xffworld", InvalidPolicy::Skip, "helloworld");
+    test_policy(b"\xff\xff\xff", InvalidPolicy::Skip, "");
+    test_policy(b"\xF0\x9F\x92\xA9\x80", InvalidPolicy::Skip, "💩");
+    test_policy(b"\xFC\x80\x80\x80\x80\x80a", InvalidPolicy::Skip, "a");
+    test_policy(b"hello\xE2\x98", InvalidPolicy::Skip, "hello");
+}
+
+/// A `Read` implementation which produces at most one byte per `read`.
+#[cfg(test)]
+struct OneByteReader<'a>(&'a [u8]);
+
+#[cfg(test)]
+impl Read for OneByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.0.len()).min(1);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+// Non-blocking input
+
+/// A `Read` implementation which produces one byte per `read`, returning
+/// `WouldBlock` before each one, like a non-blocking stream.
+#[cfg(test)]
+struct WouldBlockReader<'a> {
+    bytes: &'a [u8],
+    ready: bool,
+}
+
+#[cfg(test)]
+impl Read for WouldBlockReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.ready = !self.ready;
+        if !self.ready {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let len = buf.len().min(self.bytes.len()).min(1);
+        buf[..len].copy_from_slice(&self.bytes[..len]);
+        self.bytes = &self.bytes[len..];
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+fn read_would_block(bytes: &[u8], buf_len: usize) -> String {
+    let mut reader = Utf8Reader::new(WouldBlockReader {
+        bytes,
+        ready: false,
+    });
+    let mut would_block = 0;
+    let s = read_all(&mut reader, buf_len, |err, _| match err.kind() {
+        io::ErrorKind::WouldBlock => {
+            would_block += 1;
+            Ok(())
+        }
+        _ => Err(err),
+    })
+    .unwrap();
+    assert!(would_block >= bytes.len());
+    s
+}
+
+#[test]
+fn test_would_block() {
+    for buf_len in [4, 5, 64] {
+        assert_eq!(read_would_block(b"", buf_len), "");
+        assert_eq!(
+            read_would_block("w\u{f6}rld ☃💩".as_bytes(), buf_len),
+            "w\u{f6}rld ☃💩"
+        );
+        assert_eq!(
+            read_would_block(b"a\xF0\x9F\x92b\xE2\x98", buf_len),
+            "a\u{fffd}b\u{fffd}"
+        );
+    }
+}
+
+#[test]
+fn test_would_block_read_char() {
+    let mut reader = Utf8Reader::new(WouldBlockReader {
+        bytes: "☃💩".as_bytes(),
+        ready: false,
+    });
+    let mut chars = Vec::new();
+    loop {
+        match reader.read_char() {
+            Ok(Some(c)) => chars.push(c),
+            Ok(None) => break,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => panic!("{}", err),
+        }
+    }
+    assert_eq!(chars, ['☃', '💩']);
+}
+
+// Vectored reads
+
+#[cfg(test)]
+fn read_vectored_all<R: Read>(inner: R, lens: &[usize]) -> Vec<Vec<u8>> {
+    let mut reader = Utf8Reader::new(inner);
+    let mut reads = Vec::new();
+    loop {
+        let mut storage: Vec<Vec<u8>> = lens.iter().map(|len| vec![0; *len]).collect();
+        let mut bufs: Vec<IoSliceMut<'_>> =
+            storage.iter_mut().map(|b| IoSliceMut::new(b)).collect();
+        let mut size = match reader.read_vectored(&mut bufs) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => panic!("{}", err),
+        };
+        for buf in &storage {
+            let len = buf.len().min(size);
+            if len != 0 {
+                // No scalar value straddles two buffers.
+                str::from_utf8(&buf[..len]).unwrap();
+                reads.push(buf[..len].to_vec());
+            }
+            size -= len;
+        }
+    }
+    reads
+}
+
+#[test]
+fn test_read_vectored() {
+    let text = "w\u{f6}rld ☃💩";
+    let inputs: [&[u8]; 2] = [text.as_bytes(), b"a\xF0\x9F\x92b\xE2\x98"];
+    for inner in inputs {
+        let expected = String::from_utf8_lossy(inner);
+        for lens in [&[4, 4, 4][..], &[0, 5, 7], &[64, 64], &[6, 1, 64]] {
+            let reads = read_vectored_all(inner, lens);
+            assert_eq!(String::from_utf8(reads.concat()).unwrap(), expected);
+            let reads = read_vectored_all(OneByteReader(inner), lens);
+            assert_eq!(String::from_utf8(reads.concat()).unwrap(), expected);
+        }
+    }
+
+    // A buffer too short for the next scalar value ends the read.
+    let reads = read_vectored_all("ab☃".as_bytes(), &[4, 4]);
+    assert_eq!(reads, [b"ab".to_vec(), "☃".as_bytes().to_vec()]);
+}
+
+// Seeking
+
+#[test]
+fn test_seek() {
+    use std::io::Cursor;
+
+    let text = "a☃b💩c";
+    let mut reader = Utf8Reader::new(Cursor::new(text.as_bytes()));
+    let mut s = String::new();
+    assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+    reader.read_str_to_end(&mut s).unwrap();
+    assert_eq!(s, "b💩c");
+
+    // Seeking into the middle of a scalar value produces replacements for
+    // the rest of its encoding.
+    s.clear();
+    assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+    reader.read_str_to_end(&mut s).unwrap();
+    assert_eq!(s, "\u{fffd}\u{fffd}b💩c");
+
+    // Bytes held back as an incomplete sequence are not counted as read.
+    let mut reader = Utf8Reader::new(OneByteReaderSeek(Cursor::new(text.as_bytes())));
+    let mut buf = "\0".repeat(8);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 1);
+    assert_eq!(
+        reader.read_str(&mut buf).unwrap_err().kind(),
+        io::ErrorKind::Interrupted
+    );
+    assert_eq!(reader.stream_position().unwrap(), 1);
+
+    // Relative seeks are relative to output which has been read ahead.
+    let mut reader = Utf8Reader::new(Cursor::new(text.as_bytes()));
+    assert_eq!(reader.peek_char().unwrap(), Some('a'));
+    assert_eq!(reader.stream_position().unwrap(), 0);
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+    assert_eq!(reader.seek(SeekFrom::Current(3)).unwrap(), 4);
+    assert_eq!(reader.read_char().unwrap(), Some('b'));
+    assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 9);
+    assert_eq!(reader.read_char().unwrap(), Some('c'));
+
+    // Replacements are mapped back to the invalid sequences they replace.
+    let mut reader = Utf8Reader::new(Cursor::new(b"a\xff\xe2\x98b\xe2\x98\x83c"));
+    for (c, pos) in [
+        ('a', 1),
+        ('\u{fffd}', 2),
+        ('\u{fffd}', 4),
+        ('b', 5),
+        ('☃', 8),
+    ] {
+        assert_eq!(reader.read_char().unwrap(), Some(c));
+        assert_eq!(reader.stream_position().unwrap(), pos);
+    }
+
+    // So are invalid sequences reported as errors.
+    let mut reader = Utf8Reader::with_policy(Cursor::new(b"ab\xffc"), InvalidPolicy::Error);
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+    assert_eq!(reader.read_char().unwrap(), Some('b'));
+    assert_eq!(reader.stream_position().unwrap(), 2);
+    assert!(reader.read_char().is_err());
+    assert_eq!(reader.stream_position().unwrap(), 3);
+
+    // A scalar value split by a short `read` is partway returned.
+    let mut reader = Utf8Reader::new(Cursor::new(text.as_bytes()));
+    let mut buf = [0; 1];
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+    assert_eq!(reader.read(&mut buf).unwrap(), 1);
+    assert_eq!(reader.stream_position().unwrap(), 2);
+    assert_eq!(reader.seek(SeekFrom::Current(2)).unwrap(), 4);
+    assert_eq!(reader.read_char().unwrap(), Some('b'));
+}
+
+#[test]
+fn test_seek_to_char_boundary() {
+    use std::io::Cursor;
+
+    let text = "a☃b💩c";
+    let mut reader = Utf8Reader::new(Cursor::new(text.as_bytes())).seek_to_char_boundary(true);
+    let mut s = String::new();
+    for (pos, expected_pos, expected) in [
+        (0, 0, text),
+        (2, 4, "b💩c"),
+        (3, 4, "b💩c"),
+        (6, 9, "c"),
+        (8, 9, "c"),
+        (10, 10, ""),
+    ] {
+        s.clear();
+        assert_eq!(reader.seek(SeekFrom::Start(pos)).unwrap(), expected_pos);
+        reader.read_str_to_end(&mut s).unwrap();
+        assert_eq!(s, expected);
+    }
+
+    // UTF-16 is resynchronized by code unit.
+    let bytes = b"\xff\xfea\0\x3d\xd8\xa9\xdcb\0";
+    let mut reader = Utf8Reader::new(Cursor::new(bytes))
+        .detect_encoding(true)
+        .seek_to_char_boundary(true);
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+    assert_eq!(reader.stream_position().unwrap(), 4);
+    for (pos, expected_pos, expected) in [(3, 4, "💩b"), (5, 8, "b"), (7, 8, "b")] {
+        s.clear();
+        assert_eq!(reader.seek(SeekFrom::Start(pos)).unwrap(), expected_pos);
+        reader.read_str_to_end(&mut s).unwrap();
+        assert_eq!(s, expected);
+    }
+}
+
+#[test]
+fn test_seek_would_block() {
+    use std::io::Cursor;
+
+    // A seek which fails partway through still discards the input which was
+    // read ahead before it.
+    let mut reader = Utf8Reader::new(WouldBlockAfterSeek {
+        inner: Cursor::new("ab☃def".as_bytes()),
+        block: None,
+    })
+    .seek_to_char_boundary(true);
+    assert_eq!(reader.peek_char().unwrap(), Some('a'));
+    assert_eq!(
+        reader.seek(SeekFrom::Start(4)).unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+    let mut s = String::new();
+    reader.read_str_to_end(&mut s).unwrap();
+    assert_eq!(s, "\u{fffd}def");
+
+    // Retrying the seek succeeds.
+    s.clear();
+    assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 5);
+    reader.read_str_to_end(&mut s).unwrap();
+    assert_eq!(s, "def");
+}
+
+/// A `Read + Seek` implementation which returns `WouldBlock` from the first
+/// `read` after its first seek.
+#[cfg(test)]
+struct WouldBlockAfterSeek<'a> {
+    inner: std::io::Cursor<&'a [u8]>,
+    block: Option<bool>,
+}
+
+#[cfg(test)]
+impl Read for WouldBlockAfterSeek<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.block == Some(true) {
+            self.block = Some(false);
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+impl Seek for WouldBlockAfterSeek<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.block.get_or_insert(true);
+        self.inner.seek(pos)
+    }
+}
+
+/// A `Read + Seek` implementation which produces at most one byte per
+/// `read`.
+#[cfg(test)]
+struct OneByteReaderSeek<'a>(std::io::Cursor<&'a [u8]>);
+
+#[cfg(test)]
+impl Read for OneByteReaderSeek<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+#[cfg(test)]
+impl Seek for OneByteReaderSeek<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+// Invalid sequence callbacks
+
+#[cfg(test)]
+fn collect_invalid<R: Read>(inner: R, policy: InvalidPolicy) -> Vec<(u64, Vec<u8>, bool)> {
+    use std::sync::{Arc, Mutex};
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = Arc::clone(&events);
+    let mut reader = Utf8Reader::with_policy(inner, policy).on_invalid(move |invalid| {
+        events_clone.lock().unwrap().push((
+            invalid.offset(),
+            invalid.bytes().to_vec(),
+            invalid.is_truncated(),
+        ))
+    });
+    let mut buf = "\0".repeat(4);
+    loop {
+        match reader.read_str(&mut buf) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err)
+                if err.kind() == io::ErrorKind::Interrupted
+                    || err.kind() == io::ErrorKind::InvalidData => {}
+            Err(err) => panic!("{}", err),
+        }
+    }
+    drop(reader);
+    Arc::try_unwrap(events).unwrap().into_inner().unwrap()
+}
+
+#[cfg(test)]
+fn test_on_invalid(bytes: &[u8], expected: &[(u64, &[u8], bool)]) {
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(offset, bytes, truncated)| (*offset, bytes.to_vec(), *truncated))
+        .collect();
+    for policy in [
+        InvalidPolicy::Replace,
+        InvalidPolicy::Error,
+        InvalidPolicy::Skip,
+    ] {
+        assert_eq!(collect_invalid(bytes, policy), expected);
+        assert_eq!(
+            collect_invalid(layered_io::SliceReader::new(bytes), policy),
+            expected
+        );
+        assert_eq!(collect_invalid(OneByteReader(bytes), policy), expected);
+    }
+}
+
+#[test]
+fn test_on_invalid_none() {
+    test_on_invalid(b"hello world \xE2\x98\x83", &[]);
+}
+
+#[test]
+fn test_on_invalid_offsets() {
+    test_on_invalid(
+        b"hello\xffworld\xC0\x80",
+        &[
+            (5, b"\xff", false),
+            (11, b"\xc0", false),
+            (12, b"\x80", false),
+        ],
+    );
+    test_on_invalid(b"\xE2\x98\x83\xF0\x9F\x92\xA9\x80", &[(7, b"\x80", false)]);
+}
+
+#[test]
+fn test_on_invalid_truncated() {
+    test_on_invalid(b"hello\xE2\x98", &[(5, b"\xE2\x98", true)]);
+    test_on_invalid(
+        b"\xffab\xF0\x9F\x92",
+        &[(0, b"\xff", false), (3, b"\xF0\x9F\x92", true)],
+    );
+}
+
+// Replacement text
+
+#[cfg(test)]
+fn translate_with_replacement(
+    bytes: &[u8],
+    replacement: impl Into<Replacement>,
+    granularity: Granularity,
+    buf_len: usize,
+) -> io::Result<String> {
+    let mut reader = Utf8Reader::new(layered_io::SliceReader::new(bytes))
+        .replacement(replacement)
+        .granularity(granularity);
+    read_all(&mut reader, buf_len, |err, _| Err(err))
+}
+
+#[cfg(test)]
+fn test_replacement(
+    bytes: &[u8],
+    replacement: impl Into<Replacement> + Copy,
+    maximal_subpart: &str,
+    per_byte: &str,
+) {
+    let min_buf_len = 4.max(replacement.into().as_str().len());
+    for buf_len in [min_buf_len, min_buf_len + 1, 64] {
+        assert_eq!(
+            translate_with_replacement(bytes, replacement, Granularity::MaximalSubpart, buf_len)
+                .unwrap(),
+            maximal_subpart
+        );
+        assert_eq!(
+            translate_with_replacement(bytes, replacement, Granularity::PerByte, buf_len).unwrap(),
+            per_byte
+        );
+    }
+}
+
+#[test]
+fn test_ascii_replacement() {
+    test_replacement(b"hello\xffworld", '?', "hello?world", "hello?world");
+    test_replacement(b"hello\xffworld", "?", "hello?world", "hello?world");
+    test_replacement(b"\xF0\x9F\x92a\xE2\x98", '?', "?a?", "???a??");
+}
+
+#[test]
+fn test_empty_replacement() {
+    test_replacement(b"hello\xffworld", "", "helloworld", "helloworld");
+    test_replacement(b"\xC0\x80\xE2\x98\x83", "", "☃", "☃");
+}
+
+#[test]
+fn test_marker_replacement() {
+    test_replacement(b"a\xff\xffb", "<?>", "a<?><?>b", "a<?><?>b");
+    test_replacement(b"\xE2\x98", "<?>", "<?>", "<?><?>");
+}
+
+#[test]
+fn test_long_replacement() {
+    let replacement = "[invalid UTF-8]";
+    test_replacement(
+        b"\xff\xE2\x98\x83\xff",
+        replacement,
+        "[invalid UTF-8]☃[invalid UTF-8]",
+        "[invalid UTF-8]☃[invalid UTF-8]",
+    );
+
+    // A buffer which can't hold the replacement still makes progress.
+    for buf_len in [3, 4, 8] {
+        assert_eq!(
+            translate_with_replacement(
+                b"a\xff\xE2\x98\x83",
+                replacement,
+                Granularity::MaximalSubpart,
+                buf_len
+            )
+            .unwrap(),
+            "a[invalid UTF-8]☃"
+        );
+    }
+
+    // A `str` which can't hold the next scalar value is rejected.
+    assert_eq!(
+        translate_with_replacement(b"\xE2\x98\x83", replacement, Granularity::MaximalSubpart, 2)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
+
+#[cfg(feature = "layered-io")]
+#[test]
+fn test_replacement_minimum_buffer_size() {
+    let reader = Utf8Reader::new(layered_io::SliceReader::new(b""));
+    assert_eq!(reader.minimum_buffer_size(), 4);
+    let reader = Utf8Reader::new(layered_io::SliceReader::new(b"")).replacement('?');
+    assert_eq!(reader.minimum_buffer_size(), 4);
+    let reader = Utf8Reader::new(layered_io::SliceReader::new(b"")).replacement("[invalid]");
+    assert_eq!(reader.minimum_buffer_size(), 9);
+}
+
+// Escaping
+
+#[test]
+fn test_escape_policy() {
+    test_policy(b"hello world", InvalidPolicy::Escape, "hello world");
+    test_policy(
+        b"hello\xffworld",
+        InvalidPolicy::Escape,
+        "hello\u{10ffff}world",
+    );
+    test_policy(b"\xC0\x80", InvalidPolicy::Escape, "\u{10ffc0}\u{10ff80}");
+    test_policy(
+        b"\xE2\x98\x83\xE2\x98",
+        InvalidPolicy::Escape,
+        "☃\u{10ffe2}\u{10ff98}",
+    );
+}
+
+#[test]
+fn test_escape_policy_escapes_escapes() {
+    // U+10FF80 and U+10FFFF are escaped byte by byte.
+    test_policy(
+        b"a\xF4\x8F\xBE\x80b\xF4\x8F\xBF\xBF",
+        InvalidPolicy::Escape,
+        "a\u{10fff4}\u{10ff8f}\u{10ffbe}\u{10ff80}b\u{10fff4}\u{10ff8f}\u{10ffbf}\u{10ffbf}",
+    );
+    // Neighboring code points are not.
+    test_policy(
+        b"\xF4\x8F\xBD\xBF\xF3\xBF\xBF\xBF",
+        InvalidPolicy::Escape,
+        "\u{10ff7f}\u{fffff}",
+    );
+}
+
+// Legacy fallbacks
+
+#[test]
+fn test_windows_1252_policy() {
+    test_policy(b"caf\xe9", InvalidPolicy::Windows1252, "café");
+    test_policy(b"caf\xc3\xa9", InvalidPolicy::Windows1252, "café");
+    test_policy(
+        b"\x93quoted\x94 \x80 5\x85",
+        InvalidPolicy::Windows1252,
+        "“quoted” € 5…",
+    );
+    test_policy(
+        b"\x81\x8d\x8f\x90\x9d",
+        InvalidPolicy::Windows1252,
+        "\u{81}\u{8d}\u{8f}\u{90}\u{9d}",
+    );
+    test_policy(
+        b"na\xc3\xafve na\xefve",
+        InvalidPolicy::Windows1252,
+        "naïve naïve",
+    );
+    // Invalid sequences are decoded a byte at a time.
+    test_policy(b"\xE2\x98a\xE2\x98", InvalidPolicy::Windows1252, "â˜aâ˜");
+}
+
+#[test]
+fn test_latin_1_policy() {
+    test_policy(b"caf\xe9", InvalidPolicy::Latin1, "café");
+    test_policy(
+        b"\x93quoted\x94",
+        InvalidPolicy::Latin1,
+        "\u{93}quoted\u{94}",
+    );
+    test_policy(
+        b"\xE2\x98a\xE2\x98",
+        InvalidPolicy::Latin1,
+        "â\u{98}aâ\u{98}",
+    );
+
+    let bytes: Vec<u8> = (0x80..=0xff).collect();
+    let s: String = bytes.iter().map(|b| char::from(*b)).collect();
+    test_policy(&bytes, InvalidPolicy::Latin1, &s);
+}
+
+// Byte order marks
+
+#[cfg(test)]
+fn read_stripping_bom<R: Read>(inner: R, buf_len: usize) -> (String, Option<bool>) {
+    let mut reader = Utf8Reader::new(inner).strip_bom(true);
+    assert_eq!(reader.had_bom(), None);
+    let s = read_all(&mut reader, buf_len, |err, _| Err(err)).unwrap();
+    (s, reader.had_bom())
+}
+
+#[cfg(test)]
+fn test_bom(bytes: &[u8], s: &str, had_bom: bool) {
+    for buf_len in [4, 5, 64] {
+        let expected = (s.to_owned(), Some(had_bom));
+        assert_eq!(read_stripping_bom(bytes, buf_len), expected);
+        assert_eq!(
+            read_stripping_bom(layered_io::SliceReader::new(bytes), buf_len),
+            expected
+        );
+        assert_eq!(
+            read_stripping_bom(layered_io::LayeredReader::new(bytes), buf_len),
+            expected
+        );
+        assert_eq!(read_stripping_bom(OneByteReader(bytes), buf_len), expected);
+    }
+}
+
+#[test]
+fn test_strip_bom() {
+    test_bom(b"\xEF\xBB\xBFhello", "hello", true);
+    test_bom(b"\xEF\xBB\xBF", "", true);
+    test_bom(b"\xEF\xBB\xBF\xEF\xBB\xBFhello", "\u{feff}hello", true);
+    test_bom(b"\xEF\xBB\xBF\xE2\x98\x83", "☃", true);
+    test_bom(b"\xEF\xBB\xBF\xff", "�", true);
+}
+
+#[test]
+fn test_strip_bom_absent() {
+    test_bom(b"", "", false);
+    test_bom(b"hello", "hello", false);
+    test_bom(b"h\xEF\xBB\xBF", "h\u{feff}", false);
+    test_bom(b"\xEF\xBB", "�", false);
+    test_bom(b"\xEF\xBBa", "�a", false);
+    test_bom(b"\xEF\xBB\xBE", "\u{fefe}", false);
+}
+
+#[test]
+fn test_keep_bom() {
+    let mut reader = Utf8Reader::new(OneByteReader(b"\xEF\xBB\xBFhello"));
+    let s = read_all(&mut reader, 4, |err, _| Err(err)).unwrap();
+    assert_eq!(s, "\u{feff}hello");
+    assert_eq!(reader.had_bom(), None);
+}
+
+// Encoding detection
+
+#[cfg(test)]
+fn read_detecting_encoding<R: Read>(inner: R, buf_len: usize) -> (String, Option<Encoding>) {
+    let mut reader = Utf8Reader::new(inner).detect_encoding(true);
+    assert_eq!(reader.encoding(), None);
+    let s = read_all(&mut reader, buf_len, |err, _| Err(err)).unwrap();
+    (s, reader.encoding())
+}
+
+#[cfg(test)]
+fn test_encoding(bytes: &[u8], s: &str, encoding: Encoding) {
+    for buf_len in [4, 5, 64] {
+        let expected = (s.to_owned(), Some(encoding));
+        assert_eq!(read_detecting_encoding(bytes, buf_len), expected);
+        assert_eq!(
+            read_detecting_encoding(layered_io::SliceReader::new(bytes), buf_len),
+            expected
+        );
+        assert_eq!(
+            read_detecting_encoding(layered_io::LayeredReader::new(bytes), buf_len),
+            expected
+        );
+        assert_eq!(
+            read_detecting_encoding(OneByteReader(bytes), buf_len),
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_detect_utf8() {
+    test_encoding(b"", "", Encoding::Utf8);
+    test_encoding(b"hello", "hello", Encoding::Utf8);
+    test_encoding(b"\xEF\xBB\xBFhello", "hello", Encoding::Utf8);
+    test_encoding(b"\xFF", "\u{fffd}", Encoding::Utf8);
+    test_encoding(b"\xFEhello", "\u{fffd}hello", Encoding::Utf8);
+}
+
+#[test]
+fn test_detect_utf16() {
+    test_encoding(b"\xFF\xFE", "", Encoding::Utf16Le);
+    test_encoding(b"\xFE\xFF", "", Encoding::Utf16Be);
+    test_encoding(b"\xFF\xFEh\0i\0", "hi", Encoding::Utf16Le);
+    test_encoding(b"\xFE\xFF\0h\0i", "hi", Encoding::Utf16Be);
+    test_encoding(b"\xFF\xFE\xe9\0\x03\x26", "é☃", Encoding::Utf16Le);
+    test_encoding(b"\xFE\xFF\x26\x03\0\xe9", "☃é", Encoding::Utf16Be);
+    test_encoding(b"\xFF\xFE\x3D\xD8\x00\xDEa\0", "😀a", Encoding::Utf16Le);
+    test_encoding(b"\xFE\xFF\xD8\x3D\xDE\x00\0a", "😀a", Encoding::Utf16Be);
+}
+
+#[test]
+fn test_detect_utf16_invalid() {
+    test_encoding(b"\xFF\xFE\x3D\xD8a\0", "\u{fffd}a", Encoding::Utf16Le);
+    test_encoding(b"\xFF\xFE\x00\xDCa\0", "\u{fffd}a", Encoding::Utf16Le);
+    test_encoding(
+        b"\xFF\xFE\x00\xDC\x3D\xD8",
+        "\u{fffd}\u{fffd}",
+        Encoding::Utf16Le,
+    );
+    test_encoding(b"\xFF\xFEh\0i", "h\u{fffd}", Encoding::Utf16Le);
+    test_encoding(b"\xFF\xFE\0", "\u{fffd}", Encoding::Utf16Le);
+    test_encoding(b"\xFF\xFE\x3D\xD8\x00", "\u{fffd}", Encoding::Utf16Le);
+}
+
+#[test]
+fn test_detect_utf32() {
+    test_encoding(b"\xFF\xFE\0\0", "", Encoding::Utf32Le);
+    test_encoding(b"\0\0\xFE\xFF", "", Encoding::Utf32Be);
+    test_encoding(b"\xFF\xFE\0\0h\0\0\0", "h", Encoding::Utf32Le);
+    test_encoding(b"\0\0\xFE\xFF\0\0\0h", "h", Encoding::Utf32Be);
+    test_encoding(b"\xFF\xFE\0\0\x00\xF6\x01\0", "😀", Encoding::Utf32Le);
+    test_encoding(
+        b"\xFF\xFE\0\0\x00\xD8\0\0\0\0\x11\0h\0\0\0",
+        "\u{fffd}\u{fffd}h",
+        Encoding::Utf32Le,
+    );
+    test_encoding(b"\xFF\xFE\0\0h\0\0", "\u{fffd}", Encoding::Utf32Le);
+}
+
+#[test]
+fn test_detect_utf16_on_invalid() {
+    use std::sync::{Arc, Mutex};
+
+    let invalid = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&invalid);
+    let mut reader = Utf8Reader::with_policy(&b"\xFF\xFEh\0\x00\xDCi\0"[..], InvalidPolicy::Error)
+        .detect_encoding(true)
+        .on_invalid(move |invalid| log.lock().unwrap().push(invalid.clone()));
+
+    let mut buf = "\0".repeat(64);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 1);
+    assert_eq!(&buf[..1], "h");
+    let err = reader.read_str(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 1);
+    assert_eq!(&buf[..1], "i");
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 0);
+
+    let invalid = invalid.lock().unwrap();
+    assert_eq!(invalid.len(), 1);
+    assert_eq!(invalid[0].offset(), 4);
+    assert_eq!(invalid[0].bytes(), b"\x00\xDC");
+    assert!(!invalid[0].is_truncated());
+}
+
+// CESU-8 and Modified UTF-8
+
+#[cfg(test)]
+fn read_modified_utf8<R: Read>(inner: R, buf_len: usize) -> String {
+    let mut reader = Utf8Reader::new(inner).modified_utf8(true);
+    read_all(&mut reader, buf_len, |err, _| Err(err)).unwrap()
+}
+
+#[cfg(test)]
+fn test_modified_utf8(bytes: &[u8], s: &str) {
+    for buf_len in [4, 5, 6, 64] {
+        assert_eq!(read_modified_utf8(bytes, buf_len), s);
+        assert_eq!(
+            read_modified_utf8(layered_io::SliceReader::new(bytes), buf_len),
+            s
+        );
+        assert_eq!(
+            read_modified_utf8(layered_io::LayeredReader::new(bytes), buf_len),
+            s
+        );
+        assert_eq!(read_modified_utf8(OneByteReader(bytes), buf_len), s);
+    }
+}
+
+#[test]
+fn test_modified_utf8_surrogate_pair() {
+    test_modified_utf8(b"\xED\xA0\xBD\xED\xB2\xA9", "💩");
+    test_modified_utf8(b"ab\xED\xA0\xBD\xED\xB2\xA9cd", "ab💩cd");
+    test_modified_utf8(
+        b"\xED\xA0\x80\xED\xB0\x80\xED\xAF\xBF\xED\xBF\xBF",
+        "\u{10000}\u{10ffff}",
+    );
+    test_modified_utf8(b"\xF0\x9F\x92\xA9", "💩");
+}
+
+#[test]
+fn test_modified_utf8_nul() {
+    test_modified_utf8(b"\xC0\x80", "\0");
+    test_modified_utf8(b"a\xC0\x80b", "a\0b");
+    test_modified_utf8(b"\0", "\0");
+}
+
+#[test]
+fn test_modified_utf8_invalid() {
+    test_modified_utf8(b"\xED\xA0\xBDa", "���a");
+    test_modified_utf8(b"\xED\xB2\xA9", "���");
+    test_modified_utf8(b"\xED\xA0\xBD\xED\xB2", "�����");
+    test_modified_utf8(b"\xED\xA0\xBD\xED\xA0\xBD", "������");
+    test_modified_utf8(b"\xED\xA0\xBD\xED", "����");
+    test_modified_utf8(b"\xC0", "�");
+    test_modified_utf8(b"\xC0\x81", "��");
+    test_modified_utf8(b"\xC0\xC0\x80", "�\0");
+}
+
+// Reading a char at a time
+
+#[test]
+fn test_read_char() {
+    let bytes = b"a\xC3\xA9\xE2\x98\x83\xF0\x9F\x92\xA9\xFFb";
+    let mut reader = Utf8Reader::new(OneByteReader(bytes));
+    assert_eq!(reader.peek_char().unwrap(), Some('a'));
+    assert_eq!(reader.peek_char().unwrap(), Some('a'));
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+    assert_eq!(reader.read_char().unwrap(), Some('é'));
+    assert_eq!(reader.peek_char().unwrap(), Some('☃'));
+    assert_eq!(reader.read_char().unwrap(), Some('☃'));
+    assert_eq!(reader.read_char().unwrap(), Some('💩'));
+    assert_eq!(reader.read_char().unwrap(), Some('\u{fffd}'));
+    assert_eq!(reader.read_char().unwrap(), Some('b'));
+    assert_eq!(reader.peek_char().unwrap(), None);
+    assert_eq!(reader.read_char().unwrap(), None);
+}
+
+#[cfg(test)]
+fn read_chars<R: Read>(inner: R) -> Vec<char> {
+    Utf8Reader::new(inner)
+        .chars()
+        .collect::<io::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn test_chars() {
+    let s = "hello ☃ 💩 wörld";
+    let expected = s.chars().collect::<Vec<_>>();
+    assert_eq!(read_chars(s.as_bytes()), expected);
+    assert_eq!(read_chars(OneByteReader(s.as_bytes())), expected);
+}
+
+#[test]
+fn test_read_char_then_read_str() {
+    let mut reader = Utf8Reader::new("☃💩💩 and more".as_bytes());
+    assert_eq!(reader.read_char().unwrap(), Some('☃'));
+
+    // The rest of the read-ahead is returned first, without splitting a
+    // scalar value.
+    let mut buf = "\0".repeat(7);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], "💩");
+    assert_eq!(reader.read_char().unwrap(), Some('💩'));
+    assert_eq!(reader.read_char().unwrap(), Some(' '));
+
+    let mut s = String::new();
+    let mut buf = "\0".repeat(64);
+    loop {
+        match reader.read_str(&mut buf).unwrap() {
+            0 => break,
+            size => s.push_str(&buf[..size]),
+        }
+    }
+    assert_eq!(s, "and more");
+}
+
+#[test]
+fn test_read_char_error_policy() {
+    let mut reader = Utf8Reader::with_policy(&b"a\xFFb"[..], InvalidPolicy::Error);
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+    assert_eq!(
+        reader.read_char().unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(reader.read_char().unwrap(), Some('b'));
+    assert_eq!(reader.read_char().unwrap(), None);
+}
+
+// Short buffers
+
+/// Read `bytes` with `read` calls of `buf_len` bytes.
+#[cfg(test)]
+fn read_short(bytes: &[u8], buf_len: usize) -> Vec<u8> {
+    let mut reader = Utf8Reader::new(OneByteReader(bytes));
+    let mut v = Vec::new();
+    let mut buf = vec![0; buf_len];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(size) => v.extend_from_slice(&buf[..size]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    v
+}
+
+#[test]
+fn test_read_short() {
+    let text = "a☃b💩cö";
+    for buf_len in 1..4 {
+        assert_eq!(read_short(text.as_bytes(), buf_len), text.as_bytes());
+        assert_eq!(
+            read_short(b"a\xFFb\xE2\x98", buf_len),
+            "a\u{fffd}b\u{fffd}".as_bytes()
+        );
+    }
+}
+
+#[test]
+#[allow(clippy::unbuffered_bytes)]
+fn test_read_bytes() {
+    let bytes = Utf8Reader::new(&b"a\xE2\x98\x83\xFFb"[..])
+        .bytes()
+        .collect::<io::Result<Vec<u8>>>()
+        .unwrap();
+    assert_eq!(bytes, "a☃\u{fffd}b".as_bytes());
+}
+
+#[test]
+fn test_read_str_short() {
+    let mut reader = Utf8Reader::new("aöb☃".as_bytes());
+    let mut buf = "\0".repeat(3);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], "aö");
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 1);
+    assert_eq!(&buf[..1], "b");
+    let err = reader.read_str(&mut buf[..2]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(
+        err.get_ref()
+            .and_then(|e| e.downcast_ref::<crate::Utf8Error>()),
+        Some(&crate::Utf8Error::BufferTooSmall { len: 2, min_len: 3 })
+    );
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 3);
+    assert_eq!(buf, "☃");
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn test_split_then_read_str() {
+    let mut reader = Utf8Reader::new("☃a".as_bytes());
+    let mut byte = [0];
+    assert_eq!(reader.read(&mut byte).unwrap(), 1);
+    assert_eq!(byte, [0xE2]);
+
+    // The rest of the encoding can't be read into a `str`.
+    let mut buf = "\0".repeat(8);
+    assert_eq!(
+        reader.read_str(&mut buf).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        reader.read_char().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    let mut rest = [0; 8];
+    assert_eq!(reader.read(&mut rest).unwrap(), 2);
+    assert_eq!(rest[..2], [0x98, 0x83]);
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+}
@@ -0,0 +1,180 @@
+use crate::InvalidSequence;
+use alloc::string::String;
+use core::cmp::min;
+use core::str;
+
+/// An incremental validator which checks that a byte sequence, pushed to it
+/// a piece at a time, is valid UTF-8, in the same way as [`Utf8Writer`].
+/// Scalar values split across pieces are held until they're completed.
+///
+/// This is the output counterpart of [`Utf8Decoder`], and similarly doesn't
+/// perform any I/O itself.
+///
+/// [`Utf8Writer`]: crate::Utf8Writer
+/// [`Utf8Decoder`]: crate::Utf8Decoder
+#[derive(Debug, Default)]
+pub struct Utf8Validator {
+    /// The start of a scalar value's encoding which hasn't been completed
+    /// yet.
+    incomplete: [u8; 4],
+    incomplete_len: u8,
+
+    /// The total number of bytes validated.
+    offset: u64,
+}
+
+impl Utf8Validator {
+    /// Construct a new instance of `Utf8Validator`.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            incomplete: [0; 4],
+            incomplete_len: 0,
+            offset: 0,
+        }
+    }
+
+    /// Validate `input`, the next piece of the byte stream, appending it to
+    /// `out`, and advance `input` past the bytes consumed. Set `last` on the
+    /// final piece, so that an incomplete sequence at the end of the stream
+    /// is reported as invalid.
+    ///
+    /// If `input` contains an invalid sequence, the valid data before it is
+    /// appended to `out`, and `input` is left just after it, so validation
+    /// can continue with the rest.
+    pub fn validate(
+        &mut self,
+        input: &mut &[u8],
+        out: &mut String,
+        last: bool,
+    ) -> Result<(), InvalidSequence> {
+        // If we have incomplete bytes from the previous call, try to complete
+        // them.
+        let incomplete_len = usize::from(self.incomplete_len);
+        if incomplete_len != 0 {
+            let (copy_len, completed) = self.complete(input);
+            let (bytes, len) = match completed {
+                Some(completed) => completed,
+                None if last => self.take_incomplete(),
+                None => {
+                    *input = &input[copy_len..];
+                    return Ok(());
+                }
+            };
+
+            let result = match str::from_utf8(&bytes[..len]) {
+                Ok(s) => {
+                    out.push_str(s);
+                    self.offset += len as u64;
+                    Ok(len)
+                }
+                Err(error) => self.invalid(&bytes[..len], error, out, last),
+            };
+            // The incomplete bytes were consumed by a previous call.
+            let consumed = match &result {
+                Ok(len) | Err((len, _)) => *len,
+            };
+            *input = &input[consumed - incomplete_len..];
+            result.map_err(|(_, invalid)| invalid)?;
+        }
+
+        let bytes = *input;
+        let result = match str::from_utf8(bytes) {
+            Ok(s) => {
+                out.push_str(s);
+                self.offset += s.len() as u64;
+                Ok(bytes.len())
+            }
+            Err(error) => self.invalid(bytes, error, out, last),
+        };
+        let consumed = match &result {
+            Ok(len) | Err((len, _)) => *len,
+        };
+        *input = &bytes[consumed..];
+        result.map(|_| ()).map_err(|(_, invalid)| invalid)
+    }
+
+    /// Return the start of a scalar value's encoding held from a previous
+    /// call, which hasn't been completed yet.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn incomplete(&self) -> &[u8] {
+        &self.incomplete[..usize::from(self.incomplete_len)]
+    }
+
+    /// Hold `bytes`, the start of a scalar value's encoding at the end of
+    /// the input, until it's completed.
+    #[inline]
+    pub(crate) fn hold(&mut self, bytes: &[u8]) {
+        self.incomplete[..bytes.len()].copy_from_slice(bytes);
+        self.incomplete_len = bytes.len() as u8;
+    }
+
+    /// Stop holding the incomplete encoding, and return its bytes and
+    /// length.
+    #[inline]
+    pub(crate) fn take_incomplete(&mut self) -> ([u8; 4], usize) {
+        let len = usize::from(self.incomplete_len);
+        self.incomplete_len = 0;
+        (self.incomplete, len)
+    }
+
+    /// Continue the incomplete encoding with bytes from the start of
+    /// `input`, up to the length its first byte calls for. Return the number
+    /// of bytes taken from `input`, and, once the encoding is complete, its
+    /// bytes and length, which are no longer held. The completed encoding
+    /// isn't necessarily valid.
+    pub(crate) fn complete(&mut self, input: &[u8]) -> (usize, Option<([u8; 4], usize)>) {
+        let incomplete_len = usize::from(self.incomplete_len);
+        let utf8_len = match self.incomplete[0] & 0x30 {
+            0x20 => 3,
+            0x30 => 4,
+            _ => 2,
+        };
+        let copy_len = min(utf8_len - incomplete_len, input.len());
+        self.incomplete[incomplete_len..incomplete_len + copy_len]
+            .copy_from_slice(&input[..copy_len]);
+        self.incomplete_len = (incomplete_len + copy_len) as u8;
+
+        if incomplete_len + copy_len < utf8_len {
+            return (copy_len, None);
+        }
+        (copy_len, Some(self.take_incomplete()))
+    }
+
+    /// Handle `bytes`, which failed to validate with `error`, appending the
+    /// valid prefix to `out`, and either holding an incomplete sequence at
+    /// the end for the next call or reporting an invalid sequence. Return
+    /// the number of bytes consumed.
+    #[cold]
+    fn invalid(
+        &mut self,
+        bytes: &[u8],
+        error: str::Utf8Error,
+        out: &mut String,
+        last: bool,
+    ) -> Result<usize, (usize, InvalidSequence)> {
+        let valid_up_to = error.valid_up_to();
+        // Safety: `from_utf8` validated this part of `bytes`.
+        out.push_str(unsafe { str::from_utf8_unchecked(&bytes[..valid_up_to]) });
+        self.offset += valid_up_to as u64;
+
+        let rest = &bytes[valid_up_to..];
+        let (invalid_len, truncated) = match error.error_len() {
+            Some(invalid_len) => (invalid_len, false),
+            None if last => (rest.len(), true),
+            None => {
+                self.hold(rest);
+                return Ok(bytes.len());
+            }
+        };
+
+        let invalid = InvalidSequence {
+            offset: self.offset,
+            bytes: rest[..invalid_len].to_vec(),
+            truncated,
+        };
+        self.offset += invalid_len as u64;
+        Err((valid_up_to + invalid_len, invalid))
+    }
+}
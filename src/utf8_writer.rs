@@ -6,7 +6,7 @@ use io_extras::os::windows::{
 };
 #[cfg(feature = "layered-io")]
 use layered_io::{Bufferable, WriteLayered};
-use std::io::{self, Write};
+use std::io::{self, IoSlice, Write};
 use std::{fmt, str};
 #[cfg(feature = "terminal-io")]
 use terminal_io::{Terminal, TerminalColorSupport, WriteTerminal};
@@ -42,6 +42,20 @@ impl<Inner: Write> Utf8Writer<Inner> {
         }
     }
 
+    /// Set whether to translate the escapes produced by
+    /// [`InvalidPolicy::Escape`] back into the bytes they represent, so that
+    /// input read with a [`Utf8Reader`] using that policy is reproduced byte
+    /// for byte. With this enabled, the output is not necessarily valid
+    /// UTF-8.
+    ///
+    /// [`InvalidPolicy::Escape`]: crate::InvalidPolicy::Escape
+    /// [`Utf8Reader`]: crate::Utf8Reader
+    #[inline]
+    pub fn unescape(mut self, unescape: bool) -> Self {
+        self.output.set_unescape(unescape);
+        self
+    }
+
     /// Flush any pending output and return the inner stream.
     #[inline]
     pub fn into_inner(mut self) -> io::Result<Inner> {
@@ -101,6 +115,16 @@ impl<Inner: Write> WriteStr for Utf8Writer<Inner> {
     fn write_str(&mut self, s: &str) -> io::Result<()> {
         Utf8Output::write_str(self, s)
     }
+
+    #[inline]
+    fn write_str_partial(&mut self, s: &str) -> io::Result<usize> {
+        Utf8Output::write_str_partial(self, s)
+    }
+
+    #[inline]
+    fn write_str_vectored(&mut self, bufs: &[&str]) -> io::Result<()> {
+        Utf8Output::write_str_vectored(self, bufs)
+    }
 }
 
 #[cfg(feature = "layered-io")]
@@ -122,6 +146,11 @@ impl<Inner: Write> Write for Utf8Writer<Inner> {
         Utf8Output::write(self, buf)
     }
 
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        Utf8Output::write_vectored(self, bufs)
+    }
+
     #[inline]
     fn flush(&mut self) -> io::Result<()> {
         Utf8Output::flush(self)
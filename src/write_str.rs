@@ -1,5 +1,13 @@
+use crate::FmtAdaptor;
 use std::fmt::{self, Arguments};
-use std::io::{self, Write};
+use std::fs::File;
+use std::io::{
+    self, BufWriter, Cursor, IoSlice, LineWriter, Sink, Stderr, StderrLock, Stdout, StdoutLock,
+    Write,
+};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 
 /// Add a convenience and optimizing method for writing from `str`.
 pub trait WriteStr: Write {
@@ -9,6 +17,27 @@ pub trait WriteStr: Write {
     fn write_str(&mut self, buf: &str) -> io::Result<()> {
         default_write_str(self, buf)
     }
+
+    /// Like [`Write::write`], but takes a `&str`, and returns the length of
+    /// the prefix of `buf` written, which is at a `char` boundary, so that it
+    /// suits non-blocking streams which may fail with
+    /// [`io::ErrorKind::WouldBlock`] partway through a `write_str`.
+    ///
+    /// The default implementation can only guarantee this if the stream
+    /// accepts the rest of a scalar value's encoding when it's split; see
+    /// [`default_write_str_partial`].
+    #[inline]
+    fn write_str_partial(&mut self, buf: &str) -> io::Result<usize> {
+        default_write_str_partial(self, buf)
+    }
+
+    /// Like [`WriteStr::write_str`], but writes the concatenation of `bufs`,
+    /// using [`Write::write_vectored`], so that text built from several
+    /// pieces can be written without copying it into one buffer.
+    #[inline]
+    fn write_str_vectored(&mut self, bufs: &[&str]) -> io::Result<()> {
+        default_write_str_vectored(self, bufs)
+    }
 }
 
 /// Default implementation of [`WriteStr::write_str`], in terms of
@@ -19,34 +48,133 @@ pub fn default_write_str<Inner: Write + ?Sized>(inner: &mut Inner, buf: &str) ->
     inner.write_all(buf.as_bytes())
 }
 
+/// Default implementation of [`WriteStr::write_str_partial`], in terms of
+/// [`Write::write`].
+///
+/// Plain byte streams can't hold on to the rest of a scalar value's encoding
+/// when only part of it is written, so this tries to write the rest
+/// immediately. If the stream fails or stops accepting bytes before it's
+/// complete, as a non-blocking stream may with
+/// [`io::ErrorKind::WouldBlock`], the bytes already written can't be taken
+/// back, so this returns their number, which in that case isn't at a `char`
+/// boundary; the rest of the encoding must then be written with
+/// [`Write::write`]. Implementations with their own state, such as
+/// [`Utf8Writer`], hold the rest and write it later instead.
+///
+/// [`Utf8Writer`]: crate::Utf8Writer
+pub fn default_write_str_partial<Inner: Write + ?Sized>(
+    inner: &mut Inner,
+    buf: &str,
+) -> io::Result<usize> {
+    let mut n = inner.write(buf.as_bytes())?;
+    while !buf.is_char_boundary(n) {
+        let mut end = n;
+        while !buf.is_char_boundary(end) {
+            end += 1;
+        }
+        match inner.write(&buf.as_bytes()[n..end]) {
+            Ok(0) => break,
+            Ok(written) => n += written,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => break,
+        }
+    }
+    Ok(n)
+}
+
+/// Default implementation of [`WriteStr::write_str_vectored`], in terms of
+/// [`Write::write_vectored`].
+pub fn default_write_str_vectored<Inner: Write + ?Sized>(
+    inner: &mut Inner,
+    bufs: &[&str],
+) -> io::Result<()> {
+    let mut slices = bufs
+        .iter()
+        .map(|s| IoSlice::new(s.as_bytes()))
+        .collect::<Vec<_>>();
+    let mut slices = &mut slices[..];
+    IoSlice::advance_slices(&mut slices, 0);
+    while !slices.is_empty() {
+        match inner.write_vectored(slices) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => IoSlice::advance_slices(&mut slices, n),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
 /// Default implementation of [`Write::write_fmt`], in terms of
 /// [`WriteStr::write_str`].
 pub fn default_write_fmt<Inner: WriteStr + ?Sized>(
     inner: &mut Inner,
     fmt: Arguments,
 ) -> io::Result<()> {
-    struct Adaptor<'a, Inner: ?Sized + 'a> {
-        inner: &'a mut Inner,
-        error: Option<io::Error>,
-    }
-
-    impl<Inner: WriteStr + ?Sized> fmt::Write for Adaptor<'_, Inner> {
-        fn write_str(&mut self, s: &str) -> fmt::Result {
-            match self.inner.write_str(s) {
-                Ok(()) => Ok(()),
-                Err(e) => {
-                    self.error = Some(e);
-                    Err(fmt::Error)
-                }
-            }
-        }
+    let mut adaptor = FmtAdaptor::new(inner);
+    let result = fmt::write(&mut adaptor, fmt);
+    adaptor.io_result(result)
+}
+
+// Standard library writers have no UTF-8 requirement, so they can use the
+// default implementation, which writes the bytes straight through.
+
+impl WriteStr for Vec<u8> {}
+impl WriteStr for Cursor<Vec<u8>> {}
+impl WriteStr for Cursor<&mut Vec<u8>> {}
+impl WriteStr for Cursor<&mut [u8]> {}
+impl WriteStr for Cursor<Box<[u8]>> {}
+impl WriteStr for Sink {}
+impl WriteStr for Stdout {}
+impl WriteStr for StdoutLock<'_> {}
+impl WriteStr for Stderr {}
+impl WriteStr for StderrLock<'_> {}
+impl WriteStr for File {}
+impl WriteStr for &File {}
+impl WriteStr for TcpStream {}
+impl WriteStr for &TcpStream {}
+#[cfg(unix)]
+impl WriteStr for UnixStream {}
+#[cfg(unix)]
+impl WriteStr for &UnixStream {}
+impl<Inner: Write> WriteStr for BufWriter<Inner> {}
+impl<Inner: Write> WriteStr for LineWriter<Inner> {}
+
+impl<Inner: WriteStr + ?Sized> WriteStr for &mut Inner {
+    #[inline]
+    fn write_str(&mut self, buf: &str) -> io::Result<()> {
+        (**self).write_str(buf)
     }
 
-    let mut adapter = Adaptor { inner, error: None };
-    match fmt::write(&mut adapter, fmt) {
-        Ok(()) => Ok(()),
-        Err(_) => Err(adapter
-            .error
-            .unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "formatter error"))),
+    #[inline]
+    fn write_str_partial(&mut self, buf: &str) -> io::Result<usize> {
+        (**self).write_str_partial(buf)
+    }
+
+    #[inline]
+    fn write_str_vectored(&mut self, bufs: &[&str]) -> io::Result<()> {
+        (**self).write_str_vectored(bufs)
+    }
+}
+
+impl<Inner: WriteStr + ?Sized> WriteStr for Box<Inner> {
+    #[inline]
+    fn write_str(&mut self, buf: &str) -> io::Result<()> {
+        (**self).write_str(buf)
+    }
+
+    #[inline]
+    fn write_str_partial(&mut self, buf: &str) -> io::Result<usize> {
+        (**self).write_str_partial(buf)
+    }
+
+    #[inline]
+    fn write_str_vectored(&mut self, bufs: &[&str]) -> io::Result<()> {
+        (**self).write_str_vectored(bufs)
     }
 }
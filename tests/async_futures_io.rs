@@ -0,0 +1,162 @@
+#![cfg(feature = "futures-io")]
+
+use futures_io::{AsyncRead, AsyncWrite};
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+use utf8_io::futures_io::{copy_str, read_str, write_str, AsyncReadStr};
+use utf8_io::{Utf8AsyncDuplexer, Utf8AsyncReader, Utf8AsyncWriter};
+
+/// Run `future` to completion, polling it in a loop.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// A stream which transfers at most `chunk` bytes at a time, and is only
+/// ready on every other poll.
+struct Trickle {
+    input: Vec<u8>,
+    output: Vec<u8>,
+    chunk: usize,
+    ready: bool,
+}
+
+impl Trickle {
+    fn new(input: &[u8], chunk: usize) -> Self {
+        Self {
+            input: input.to_vec(),
+            output: Vec::new(),
+            chunk,
+            ready: false,
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<()> {
+        self.ready = !self.ready;
+        if self.ready {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl AsyncRead for Trickle {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.poll_ready().is_pending() {
+            return Poll::Pending;
+        }
+        let len = self.chunk.min(self.input.len()).min(buf.len());
+        buf[..len].copy_from_slice(&self.input[..len]);
+        self.input.drain(..len);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncWrite for Trickle {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.poll_ready().is_pending() {
+            return Poll::Pending;
+        }
+        let len = self.chunk.min(buf.len());
+        self.output.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Read all of `reader` with `read_str`, dropping the future each time it's
+/// pending.
+fn read_all_cancelling<R: AsyncReadStr + Unpin>(reader: &mut R, buf_len: usize) -> String {
+    let mut s = String::new();
+    let mut buf = "\0".repeat(buf_len);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        let result = pin!(read_str(reader, &mut buf)).poll(&mut cx);
+        match result {
+            Poll::Ready(Ok(0)) => return s,
+            Poll::Ready(Ok(size)) => s.push_str(&buf[..size]),
+            Poll::Ready(Err(err)) => panic!("{}", err),
+            Poll::Pending => {}
+        }
+    }
+}
+
+#[test]
+fn read_cancellation() {
+    let input = b"a\xe2\x98\x83b\xff\xf0\x9f\x92\xa9\xf0\x9f";
+    for chunk in [1, 2, 64] {
+        let mut reader = Utf8AsyncReader::new(Trickle::new(input, chunk));
+        assert_eq!(read_all_cancelling(&mut reader, 4), "a☃b\u{fffd}💩\u{fffd}");
+    }
+
+    let mut reader = Utf8AsyncReader::new(&b"\xe2\x98\x83\xff"[..]);
+    let mut buf = [0; 8];
+    let n = block_on(poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf))).unwrap();
+    assert_eq!(&buf[..n], "☃\u{fffd}".as_bytes());
+}
+
+#[test]
+fn write_bytes() {
+    let bytes = "a☃💩".as_bytes();
+    let mut writer = Utf8AsyncWriter::new(Trickle::new(b"", 1));
+    for byte in bytes {
+        let n = block_on(poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, &[*byte]))).unwrap();
+        assert_eq!(n, 1);
+    }
+    block_on(write_str(&mut writer, " ok")).unwrap();
+    block_on(poll_fn(|cx| Pin::new(&mut writer).poll_close(cx))).unwrap();
+    assert_eq!(writer.into_inner().output, "a☃💩 ok".as_bytes());
+
+    let mut writer = Utf8AsyncWriter::new(Vec::new());
+    block_on(poll_fn(|cx| {
+        Pin::new(&mut writer).poll_write(cx, b"\xf0\x9f")
+    }))
+    .unwrap();
+    assert_eq!(
+        block_on(poll_fn(|cx| Pin::new(&mut writer).poll_close(cx)))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn copy() {
+    let text = "hello w\u{f6}rld ☃💩";
+    let mut reader = Utf8AsyncReader::new(text.as_bytes());
+    let mut writer = Utf8AsyncWriter::new(Vec::new());
+    let n = block_on(copy_str(&mut reader, &mut writer)).unwrap();
+    assert_eq!(n, text.len() as u64);
+    assert_eq!(writer.into_inner(), text.as_bytes());
+}
+
+#[test]
+fn duplexer() {
+    let mut duplexer = Utf8AsyncDuplexer::new(Trickle::new("☃ in".as_bytes(), 1));
+    block_on(write_str(&mut duplexer, "💩 out")).unwrap();
+    assert_eq!(read_all_cancelling(&mut duplexer, 4), "☃ in");
+    assert_eq!(duplexer.into_inner().output, "💩 out".as_bytes());
+}
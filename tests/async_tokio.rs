@@ -0,0 +1,253 @@
+#![cfg(feature = "tokio")]
+
+use std::future::{poll_fn, Future};
+use std::io::{self, Read};
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use utf8_io::tokio::{copy_str, read_str, write_str, AsyncReadStr, AsyncWriteStr};
+use utf8_io::{InvalidPolicy, Utf8AsyncDuplexer, Utf8AsyncReader, Utf8AsyncWriter, Utf8Reader};
+
+/// Run `future` to completion, polling it in a loop.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// A stream which transfers at most `chunk` bytes at a time, and is only
+/// ready on every other poll.
+struct Trickle {
+    input: Vec<u8>,
+    output: Vec<u8>,
+    chunk: usize,
+    ready: bool,
+}
+
+impl Trickle {
+    fn new(input: &[u8], chunk: usize) -> Self {
+        Self {
+            input: input.to_vec(),
+            output: Vec::new(),
+            chunk,
+            ready: false,
+        }
+    }
+
+    fn poll_ready(&mut self) -> Poll<()> {
+        self.ready = !self.ready;
+        if self.ready {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl AsyncRead for Trickle {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.poll_ready().is_pending() {
+            return Poll::Pending;
+        }
+        let len = self.chunk.min(self.input.len()).min(buf.remaining());
+        buf.put_slice(&self.input[..len]);
+        self.input.drain(..len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Trickle {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.poll_ready().is_pending() {
+            return Poll::Pending;
+        }
+        let len = self.chunk.min(buf.len());
+        self.output.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Read all of `reader` with `read_str`, dropping the future each time it's
+/// pending.
+fn read_all_cancelling<R: AsyncReadStr + Unpin>(reader: &mut R, buf_len: usize) -> String {
+    let mut s = String::new();
+    let mut buf = "\0".repeat(buf_len);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        let result = pin!(read_str(reader, &mut buf)).poll(&mut cx);
+        match result {
+            Poll::Ready(Ok(0)) => return s,
+            Poll::Ready(Ok(size)) => s.push_str(&buf[..size]),
+            Poll::Ready(Err(err)) => panic!("{}", err),
+            Poll::Pending => {}
+        }
+    }
+}
+
+fn translate_sync(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    Utf8Reader::new(bytes).read_to_string(&mut s).unwrap();
+    s
+}
+
+const INPUTS: &[&[u8]] = &[
+    b"hello world",
+    "w\u{f6}rld ☃💩".as_bytes(),
+    b"a\xe2\x98b\xff\xfe\xf0\x9f\x92",
+    b"\xef\xbb\xbfbom",
+    b"\xed\xa0\x80\xed\xb0\x80\xc0\x80",
+];
+
+#[test]
+fn read_cancellation() {
+    for input in INPUTS {
+        for chunk in [1, 2, 3, 64] {
+            for buf_len in [4, 5, 64] {
+                let mut reader = Utf8AsyncReader::new(Trickle::new(input, chunk));
+                assert_eq!(
+                    read_all_cancelling(&mut reader, buf_len),
+                    translate_sync(input),
+                    "{:?} chunk {} buf_len {}",
+                    input,
+                    chunk,
+                    buf_len
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn read_options() {
+    let input = b"\xef\xbb\xbfa\xffb";
+    let mut reader = Utf8AsyncReader::new(Trickle::new(input, 1))
+        .strip_bom(true)
+        .replacement("?");
+    assert_eq!(read_all_cancelling(&mut reader, 4), "a?b");
+    assert_eq!(reader.had_bom(), Some(true));
+
+    let input = b"\xff\xfea\x00\x03\x26\x3d\xd8\xa9\xdc";
+    for chunk in [1, 3] {
+        let mut reader = Utf8AsyncReader::new(Trickle::new(input, chunk)).detect_encoding(true);
+        assert_eq!(read_all_cancelling(&mut reader, 4), "a☃💩");
+    }
+
+    let mut reader = Utf8AsyncReader::with_policy(Trickle::new(b"a\xffb", 1), InvalidPolicy::Error);
+    let mut buf = "\0".repeat(4);
+    assert_eq!(block_on(read_str(&mut reader, &mut buf)).unwrap(), 1);
+    assert_eq!(
+        block_on(read_str(&mut reader, &mut buf))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn read_bytes() {
+    let mut reader = Utf8AsyncReader::new(Trickle::new(b"\xe2\x98\x83\xff", 1));
+    let mut v = Vec::new();
+    let mut buf = [0; 4];
+    loop {
+        let mut read_buf = ReadBuf::new(&mut buf);
+        block_on(poll_fn(|cx| {
+            Pin::new(&mut reader).poll_read(cx, &mut read_buf)
+        }))
+        .unwrap();
+        if read_buf.filled().is_empty() {
+            break;
+        }
+        std::str::from_utf8(read_buf.filled()).unwrap();
+        v.extend_from_slice(read_buf.filled());
+    }
+    assert_eq!(v, "☃\u{fffd}".as_bytes());
+}
+
+#[test]
+fn write_cancellation() {
+    let text = "a☃💩 w\u{f6}rld";
+    let mut writer = Utf8AsyncWriter::new(Trickle::new(b"", 1));
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut s = text;
+    while !s.is_empty() {
+        let mut writer = Pin::new(&mut writer);
+        if let Poll::Ready(n) = writer.as_mut().poll_write_str(&mut cx, s) {
+            let n = n.unwrap();
+            assert!(s.is_char_boundary(n));
+            s = &s[n..];
+        }
+    }
+    block_on(poll_fn(|cx| Pin::new(&mut writer).poll_flush(cx))).unwrap();
+    assert_eq!(writer.get_ref().output, text.as_bytes());
+}
+
+#[test]
+fn write_bytes() {
+    let bytes = "a☃💩".as_bytes();
+    let mut writer = Utf8AsyncWriter::new(Trickle::new(b"", 2));
+    for byte in bytes {
+        let n = block_on(poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, &[*byte]))).unwrap();
+        assert_eq!(n, 1);
+    }
+    block_on(write_str(&mut writer, " ok")).unwrap();
+    block_on(poll_fn(|cx| Pin::new(&mut writer).poll_shutdown(cx))).unwrap();
+    assert_eq!(writer.into_inner().output, "a☃💩 ok".as_bytes());
+
+    let mut writer = Utf8AsyncWriter::new(Vec::new());
+    let result = block_on(poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, b"\xff")));
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    let mut writer = Utf8AsyncWriter::new(Vec::new());
+    block_on(poll_fn(|cx| {
+        Pin::new(&mut writer).poll_write(cx, b"\xe2\x98")
+    }))
+    .unwrap();
+    assert_eq!(
+        block_on(write_str(&mut writer, "x")).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(
+        block_on(poll_fn(|cx| Pin::new(&mut writer).poll_flush(cx)))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn copy() {
+    let text = "hello w\u{f6}rld ☃💩";
+    let mut reader = Utf8AsyncReader::new(Trickle::new(text.as_bytes(), 3));
+    let mut writer = Utf8AsyncWriter::new(Trickle::new(b"", 2));
+    let n = block_on(copy_str(&mut reader, &mut writer)).unwrap();
+    assert_eq!(n, text.len() as u64);
+    assert_eq!(writer.get_ref().output, text.as_bytes());
+}
+
+#[test]
+fn duplexer() {
+    let mut duplexer = Utf8AsyncDuplexer::new(Trickle::new("☃ in".as_bytes(), 1));
+    block_on(write_str(&mut duplexer, "💩 out")).unwrap();
+    assert_eq!(read_all_cancelling(&mut duplexer, 4), "☃ in");
+    assert_eq!(duplexer.into_inner().output, "💩 out".as_bytes());
+}
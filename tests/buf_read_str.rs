@@ -0,0 +1,170 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, BufRead, Read};
+use utf8_io::{BufReadStr, ReadStr, Utf8BufReader, Utf8Reader};
+
+const TEXT: &[u8] = b"hello\nw\xC3\xB6rld\r\n\xE2\x98\x83\xF0\x9F\x92\xA9\n\nlast";
+
+fn reader(bytes: &[u8], capacity: usize) -> Utf8BufReader<Utf8Reader<&[u8]>> {
+    Utf8BufReader::with_capacity(capacity, Utf8Reader::new(bytes))
+}
+
+#[test]
+fn read_line_str() {
+    for capacity in [4, 5, 7, 64] {
+        let mut reader = reader(TEXT, capacity);
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line_str(&mut line).unwrap() == 0 {
+                break;
+            }
+            lines.push(line);
+        }
+        assert_eq!(lines, ["hello\n", "wörld\r\n", "☃💩\n", "\n", "last"]);
+    }
+}
+
+#[test]
+fn lines_str() {
+    for capacity in [4, 5, 7, 64] {
+        let lines = reader(TEXT, capacity)
+            .lines_str()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines, ["hello", "wörld", "☃💩", "", "last"]);
+    }
+}
+
+#[test]
+fn invalid_input_is_replaced() {
+    let lines = reader(b"a\xffb\nc\xe2\x98", 64)
+        .lines_str()
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(lines, ["a\u{fffd}b", "c\u{fffd}"]);
+}
+
+#[test]
+fn fill_buf_str_and_consume() {
+    let mut reader = reader("☃💩".as_bytes(), 64);
+    assert_eq!(reader.fill_buf_str().unwrap(), "☃💩");
+    reader.consume("☃".len());
+    assert_eq!(reader.buffer_str(), "💩");
+    assert_eq!(reader.fill_buf_str().unwrap(), "💩");
+    reader.consume("💩".len());
+    assert_eq!(reader.fill_buf_str().unwrap(), "");
+}
+
+#[test]
+fn consume_part_of_a_scalar_value() {
+    let mut reader = reader("☃a".as_bytes(), 64);
+    reader.fill_buf_str().unwrap();
+    reader.consume(1);
+    assert_eq!(
+        reader.fill_buf_str().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(reader.fill_buf().unwrap(), b"\x98\x83a");
+    reader.consume(2);
+    assert_eq!(reader.fill_buf_str().unwrap(), "a");
+}
+
+#[test]
+fn read_bytes() {
+    for capacity in [4, 5, 64] {
+        let bytes = reader(TEXT, capacity)
+            .bytes()
+            .collect::<io::Result<Vec<u8>>>();
+        assert_eq!(bytes.unwrap(), TEXT);
+    }
+
+    let mut reader = reader("a☃".as_bytes(), 64);
+    let mut buf = [0; 2];
+    assert_eq!(reader.read(&mut buf).unwrap(), 1);
+    assert_eq!(reader.read(&mut buf).unwrap(), 2);
+    assert_eq!(buf, [0xe2, 0x98]);
+    assert_eq!(
+        reader.read_char().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(reader.read(&mut buf).unwrap(), 1);
+    assert_eq!(buf[0], 0x83);
+    assert_eq!(reader.read_char().unwrap(), None);
+}
+
+#[test]
+fn read_str_does_not_split_scalar_values() {
+    for capacity in [4, 5, 64] {
+        let mut reader = reader(TEXT, capacity);
+        let mut s = String::new();
+        let mut buf = "\0".repeat(5);
+        loop {
+            match reader.read_str(&mut buf) {
+                Ok(0) => break,
+                Ok(size) => s.push_str(&buf[..size]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => panic!("{}", err),
+            }
+        }
+        assert_eq!(s.as_bytes(), TEXT);
+    }
+}
+
+#[test]
+fn read_to_string() {
+    for capacity in [4, 5, 64] {
+        let mut s = String::new();
+        reader(TEXT, capacity).read_to_string(&mut s).unwrap();
+        assert_eq!(s.as_bytes(), TEXT);
+    }
+}
+
+#[test]
+fn read_char() {
+    for capacity in [4, 5, 64] {
+        let mut reader = reader(TEXT, capacity);
+        assert_eq!(reader.peek_char().unwrap(), Some('h'));
+        let chars = reader.chars().collect::<io::Result<String>>().unwrap();
+        assert_eq!(chars.as_bytes(), TEXT);
+        assert_eq!(reader.peek_char().unwrap(), None);
+    }
+}
+
+#[test]
+fn read_char_default() {
+    /// A `ReadStr` which can't peek, relying on the default `read_char`.
+    struct Unbuffered<'a>(Utf8Reader<&'a [u8]>);
+
+    impl Read for Unbuffered<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl ReadStr for Unbuffered<'_> {
+        fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+            self.0.read_str(buf)
+        }
+    }
+
+    let unbuffered = |bytes| Unbuffered(Utf8Reader::new(bytes));
+
+    let chars = unbuffered(TEXT).chars().collect::<io::Result<String>>();
+    assert_eq!(chars.unwrap().as_bytes(), TEXT);
+
+    let mut reader = unbuffered(b"a\xFFb");
+    assert_eq!(
+        reader.peek_char().unwrap_err().kind(),
+        io::ErrorKind::Unsupported
+    );
+    assert_eq!(reader.read_char().unwrap(), Some('a'));
+    assert_eq!(reader.read_char().unwrap(), Some('\u{fffd}'));
+    assert_eq!(reader.read_char().unwrap(), Some('b'));
+    assert_eq!(reader.read_char().unwrap(), None);
+
+    let mut reader = Utf8BufReader::new(unbuffered(TEXT));
+    assert_eq!(reader.peek_char().unwrap(), Some('h'));
+    let chars = reader.chars().collect::<io::Result<String>>();
+    assert_eq!(chars.unwrap().as_bytes(), TEXT);
+}
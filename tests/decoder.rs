@@ -0,0 +1,177 @@
+#[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(feature = "std")]
+use utf8_io::Utf8Reader;
+use utf8_io::{Encoding, InvalidPolicy, Utf8Decoder, Utf8Validator};
+
+#[cfg(feature = "std")]
+const INPUTS: &[&[u8]] = &[
+    b"",
+    b"hello world",
+    "w\u{f6}rld ☃💩".as_bytes(),
+    b"a\xe2\x98b\xff\xfe\xf0\x9f\x92",
+    b"\xef\xbb\xbfbom",
+    b"\xed\xa0\x80\xed\xb0\x80\xc0\x80",
+    b"\xf0\x9f\x92",
+];
+
+/// Decode `input` in pieces of `chunk` bytes.
+fn decode_chunked(mut decoder: Utf8Decoder, input: &[u8], chunk: usize) -> String {
+    let mut s = String::new();
+    for mut piece in input.chunks(chunk) {
+        decoder.decode(&mut piece, &mut s, false).unwrap();
+        assert!(piece.is_empty());
+    }
+    decoder.decode(&mut &b""[..], &mut s, true).unwrap();
+    s
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn decode_matches_reader() {
+    for input in INPUTS {
+        let mut expected = String::new();
+        Utf8Reader::new(*input)
+            .read_to_string(&mut expected)
+            .unwrap();
+        for chunk in [1, 2, 3, 64] {
+            assert_eq!(
+                decode_chunked(Utf8Decoder::new(), input, chunk),
+                expected,
+                "{:?} chunk {}",
+                input,
+                chunk
+            );
+        }
+    }
+}
+
+#[test]
+fn decode_options() {
+    for chunk in [1, 2, 64] {
+        let decoder = Utf8Decoder::new().strip_bom(true).replacement("?");
+        assert_eq!(
+            decode_chunked(decoder, b"\xef\xbb\xbfa\xffb\xe2\x98", chunk),
+            "a?b?"
+        );
+
+        let decoder = Utf8Decoder::new().detect_encoding(true);
+        assert_eq!(
+            decode_chunked(decoder, b"\xff\xfea\x00\x03\x26\x3d\xd8\xa9\xdc", chunk),
+            "a☃💩"
+        );
+
+        let decoder = Utf8Decoder::new().modified_utf8(true);
+        assert_eq!(
+            decode_chunked(decoder, b"\xed\xa0\xbd\xed\xb2\xa9\xc0\x80", chunk),
+            "💩\0"
+        );
+    }
+
+    let mut decoder = Utf8Decoder::new().detect_encoding(true);
+    let mut s = String::new();
+    decoder.decode(&mut &b"\xff"[..], &mut s, false).unwrap();
+    assert_eq!(decoder.encoding(), None);
+    // This could still be the start of a UTF-32 byte order mark.
+    decoder.decode(&mut &b"\xfe"[..], &mut s, false).unwrap();
+    assert_eq!(decoder.encoding(), None);
+    decoder.decode(&mut &b"a\x00"[..], &mut s, false).unwrap();
+    assert_eq!(decoder.encoding(), Some(Encoding::Utf16Le));
+    assert_eq!(s, "a");
+    assert_eq!(decoder.had_bom(), Some(true));
+}
+
+#[test]
+fn decode_error_policy() {
+    let mut decoder = Utf8Decoder::with_policy(InvalidPolicy::Error);
+    let mut s = String::new();
+    let mut input = &b"a\xffb\xe2"[..];
+    let invalid = decoder.decode(&mut input, &mut s, false).unwrap_err();
+    assert_eq!(s, "a");
+    assert_eq!(invalid.offset(), 1);
+    assert_eq!(invalid.bytes(), b"\xff");
+    assert_eq!(input, b"b\xe2");
+
+    // Decoding continues after the invalid sequence.
+    decoder.decode(&mut input, &mut s, false).unwrap();
+    assert!(input.is_empty());
+    decoder.decode(&mut &b"\x98"[..], &mut s, false).unwrap();
+    assert_eq!(s, "ab");
+    let invalid = decoder.decode(&mut &b""[..], &mut s, true).unwrap_err();
+    assert_eq!(invalid.offset(), 3);
+    assert_eq!(invalid.bytes(), b"\xe2\x98");
+    assert!(invalid.is_truncated());
+    decoder.decode(&mut &b""[..], &mut s, true).unwrap();
+    assert_eq!(s, "ab");
+}
+
+#[test]
+fn decode_long_input() {
+    let input = "☃💩 hello w\u{f6}rld ".repeat(1000);
+    let mut decoder = Utf8Decoder::new();
+    let mut s = String::new();
+    decoder.decode(&mut input.as_bytes(), &mut s, true).unwrap();
+    assert_eq!(s, input);
+
+    let invalid = b"\xff".repeat(100);
+    let mut decoder = Utf8Decoder::new();
+    let mut s = String::new();
+    decoder.decode(&mut &invalid[..], &mut s, true).unwrap();
+    assert_eq!(s, "\u{fffd}".repeat(100));
+}
+
+#[test]
+fn validate() {
+    let text = "a☃💩 w\u{f6}rld";
+    for chunk in [1, 2, 3, 64] {
+        let mut validator = Utf8Validator::new();
+        let mut s = String::new();
+        for mut piece in text.as_bytes().chunks(chunk) {
+            validator.validate(&mut piece, &mut s, false).unwrap();
+        }
+        validator.validate(&mut &b""[..], &mut s, true).unwrap();
+        assert_eq!(s, text);
+    }
+}
+
+#[test]
+fn validate_invalid() {
+    let mut validator = Utf8Validator::new();
+    let mut s = String::new();
+    let mut input = &b"ab\xffcd"[..];
+    let invalid = validator.validate(&mut input, &mut s, false).unwrap_err();
+    assert_eq!(s, "ab");
+    assert_eq!(invalid.offset(), 2);
+    assert_eq!(invalid.bytes(), b"\xff");
+    assert!(!invalid.is_truncated());
+    validator.validate(&mut input, &mut s, false).unwrap();
+    assert_eq!(s, "abcd");
+
+    // An incomplete sequence which turns out to be invalid.
+    let mut validator = Utf8Validator::new();
+    let mut s = String::new();
+    validator
+        .validate(&mut &b"a\xe2\x98"[..], &mut s, false)
+        .unwrap();
+    assert_eq!(s, "a");
+    let invalid = validator
+        .validate(&mut &b"b"[..], &mut s, false)
+        .unwrap_err();
+    assert_eq!(invalid.offset(), 1);
+    assert_eq!(invalid.bytes(), b"\xe2\x98");
+    assert!(!invalid.is_truncated());
+
+    // An incomplete sequence at the end.
+    let mut validator = Utf8Validator::new();
+    let mut s = String::new();
+    validator
+        .validate(&mut &b"a\xf0\x9f"[..], &mut s, false)
+        .unwrap();
+    let invalid = validator
+        .validate(&mut &b"\x92"[..], &mut s, true)
+        .unwrap_err();
+    assert_eq!(s, "a");
+    assert_eq!(invalid.offset(), 1);
+    assert_eq!(invalid.bytes(), b"\xf0\x9f\x92");
+    assert!(invalid.is_truncated());
+}
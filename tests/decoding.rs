@@ -0,0 +1,202 @@
+#![cfg(feature = "encoding_rs")]
+
+use encoding_rs::{Encoding, EUC_KR, GBK, ISO_8859_2, SHIFT_JIS, UTF_16LE, WINDOWS_1252};
+use std::io::{self, Read};
+use utf8_io::{copy_str, DecodingReader, ReadStr, Utf8Writer};
+
+/// A reader which returns at most one byte per `read`.
+struct OneByteReader<'a>(&'a [u8]);
+
+impl Read for OneByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.0.len()).min(1);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+fn decode_with_buffer<R: Read>(mut reader: DecodingReader<R>, buf_len: usize) -> String {
+    let mut s = String::new();
+    let mut buf = "\0".repeat(buf_len);
+    loop {
+        match reader.read_str(&mut buf) {
+            Ok(0) => break,
+            Ok(size) => s.push_str(&buf[..size]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    s
+}
+
+fn test(bytes: &[u8], encoding: &'static Encoding, s: &str) {
+    for buf_len in [4, 5, 7, 64] {
+        assert_eq!(
+            decode_with_buffer(DecodingReader::new(bytes, encoding), buf_len),
+            s
+        );
+        assert_eq!(
+            decode_with_buffer(DecodingReader::new(OneByteReader(bytes), encoding), buf_len),
+            s
+        );
+    }
+}
+
+#[test]
+fn shift_jis() {
+    test(
+        b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd",
+        SHIFT_JIS,
+        "こんにちは",
+    );
+}
+
+#[test]
+fn gbk() {
+    test(b"\xc4\xe3\xba\xc3", GBK, "你好");
+}
+
+#[test]
+fn euc_kr() {
+    test(b"\xbe\xc8\xb3\xe7", EUC_KR, "안녕");
+}
+
+#[test]
+fn iso_8859_2() {
+    test(b"\xb3\xf3d\xbc", ISO_8859_2, "łódź");
+}
+
+#[test]
+fn windows_1252() {
+    test(
+        b"\x93quoted\x94 \x80",
+        WINDOWS_1252,
+        "\u{201c}quoted\u{201d} €",
+    );
+}
+
+#[test]
+fn malformed() {
+    test(b"a\x82", SHIFT_JIS, "a\u{fffd}");
+    test(b"\xff\xfea", UTF_16LE, "\u{fffd}");
+}
+
+#[test]
+fn bom_sniffing() {
+    test(b"\xef\xbb\xbf\xc3\xa9", SHIFT_JIS, "é");
+    test(b"\xff\xfe\xe9\0", SHIFT_JIS, "é");
+
+    let mut reader = DecodingReader::new(&b"\xff\xfeh\0"[..], SHIFT_JIS);
+    assert_eq!(reader.encoding(), SHIFT_JIS);
+    let mut s = String::new();
+    reader.read_to_string(&mut s).unwrap();
+    assert_eq!(s, "h");
+    assert_eq!(reader.encoding(), UTF_16LE);
+}
+
+#[test]
+fn small_buffer() {
+    let mut reader = DecodingReader::new(&b"abc"[..], WINDOWS_1252);
+    let mut buf = [0; 3];
+    assert_eq!(
+        reader.read(&mut buf).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
+
+#[test]
+fn read_char() {
+    let bytes = b"\x82\xb1\x82\xf1abc";
+    let mut reader = DecodingReader::new(&bytes[..], SHIFT_JIS);
+    assert_eq!(reader.peek_char().unwrap(), Some('こ'));
+    assert_eq!(reader.read_char().unwrap(), Some('こ'));
+    assert_eq!(reader.read_char().unwrap(), Some('ん'));
+    assert_eq!(reader.peek_char().unwrap(), Some('a'));
+
+    // Output decoded while peeking is returned before any more.
+    let mut buf = "\0".repeat(4);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], "abc");
+    assert_eq!(reader.peek_char().unwrap(), None);
+    assert_eq!(reader.read_char().unwrap(), None);
+
+    let s = DecodingReader::new(OneByteReader(bytes), SHIFT_JIS)
+        .chars()
+        .collect::<io::Result<String>>()
+        .unwrap();
+    assert_eq!(s, "こんabc");
+}
+
+#[test]
+fn default_read_char() {
+    /// A `ReadStr` which only implements `read_str`, to exercise the default
+    /// `read_char` with a stream which needs 4-byte buffers.
+    struct Basic<Inner>(Inner);
+
+    impl<Inner: ReadStr> Read for Basic<Inner> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl<Inner: ReadStr> ReadStr for Basic<Inner> {
+        fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+            self.0.read_str(buf)
+        }
+    }
+
+    let mut reader = Basic(DecodingReader::new(&b"\x82\xb1\x82\xf1"[..], SHIFT_JIS));
+    assert_eq!(reader.read_char().unwrap(), Some('こ'));
+    assert_eq!(reader.read_char().unwrap(), Some('ん'));
+    assert_eq!(reader.read_char().unwrap(), None);
+}
+
+#[test]
+fn read_to_string() {
+    let bytes = b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd".repeat(1000);
+    let expected = "こんにちは".repeat(1000);
+    for prefix in ["", "abc"] {
+        let mut s = prefix.to_owned();
+        let mut reader = DecodingReader::new(&bytes[..], SHIFT_JIS);
+        assert_eq!(reader.read_to_string(&mut s).unwrap(), expected.len());
+        assert_eq!(s, prefix.to_owned() + &expected);
+
+        let mut s = prefix.to_owned();
+        let mut reader = DecodingReader::new(OneByteReader(&bytes), SHIFT_JIS);
+        assert_eq!(reader.read_to_string(&mut s).unwrap(), expected.len());
+        assert_eq!(s, prefix.to_owned() + &expected);
+    }
+}
+
+#[test]
+fn copy_to_utf8_writer() {
+    let mut reader = DecodingReader::new(&b"\x82\xb1\x82\xf1"[..], SHIFT_JIS);
+    let mut writer = Utf8Writer::new(Vec::new());
+    copy_str(&mut reader, &mut writer).unwrap();
+    assert_eq!(writer.into_inner().unwrap(), "こん".as_bytes());
+}
+
+#[cfg(feature = "layered-io")]
+#[test]
+fn read_with_status() {
+    use layered_io::{ReadLayered, SliceReader, Status};
+    use utf8_io::ReadStrLayered;
+
+    let mut reader = DecodingReader::new(SliceReader::new(b"\x82\xb1\x82\xf1"), SHIFT_JIS);
+    assert_eq!(reader.minimum_buffer_size(), 4);
+    let mut s = String::new();
+    let mut buf = "\0".repeat(4);
+    loop {
+        let (size, status) = reader.read_str_with_status(&mut buf).unwrap();
+        s.push_str(&buf[..size]);
+        if status.is_end() {
+            break;
+        }
+    }
+    assert_eq!(s, "こん");
+    assert_eq!(
+        reader.read_with_status(&mut [0; 4]).unwrap(),
+        (0, Status::End)
+    );
+}
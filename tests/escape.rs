@@ -0,0 +1,76 @@
+#![cfg(feature = "std")]
+
+use std::io::Write;
+use utf8_io::{copy_str, InvalidPolicy, Utf8Reader, Utf8Writer};
+
+fn round_trip(bytes: &[u8]) -> Vec<u8> {
+    let mut reader = Utf8Reader::with_policy(bytes, InvalidPolicy::Escape);
+    let mut writer = Utf8Writer::new(Vec::new()).unescape(true);
+    copy_str(&mut reader, &mut writer).unwrap();
+    writer.into_inner().unwrap()
+}
+
+#[test]
+fn round_trip_valid() {
+    let bytes = "hello world ☃ 💩".as_bytes();
+    assert_eq!(round_trip(bytes), bytes);
+}
+
+#[test]
+fn round_trip_invalid() {
+    for bytes in [
+        &b"hello\xffworld"[..],
+        b"\xC0\x80",
+        b"\xED\xA0\xBD\xED\xB2\xA9",
+        b"\xF8\x84\x8F\xBF\xBF",
+        b"\x80\x80\x80\x80\x80\x80\x80",
+        b"\xF0\x9F\x92",
+        b"hello\xE2\x98",
+    ] {
+        assert_eq!(round_trip(bytes), bytes);
+    }
+}
+
+#[test]
+fn round_trip_escape_code_points() {
+    let bytes = "\u{10ff7f}\u{10ff80}\u{10ffbf}\u{10ffc0}\u{10ffff}".as_bytes();
+    assert_eq!(round_trip(bytes), bytes);
+}
+
+#[test]
+fn round_trip_all_bytes() {
+    let bytes: Vec<u8> = (0..=255).chain((0..=255).rev()).collect();
+    assert_eq!(round_trip(&bytes), bytes);
+}
+
+#[test]
+fn round_trip_pseudorandom() {
+    let mut state = 0x2545_f491_u32;
+    let bytes: Vec<u8> = (0..64 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            // Favor bytes with the high bit set, to exercise more encodings.
+            (state as u8) | ((state >> 8) as u8 & 0x80)
+        })
+        .collect();
+    assert_eq!(round_trip(&bytes), bytes);
+}
+
+#[test]
+fn unescape_split_across_writes() {
+    let mut writer = Utf8Writer::new(Vec::new()).unescape(true);
+    let escaped = "a\u{10ffff}b".as_bytes();
+    for byte in escaped {
+        writer.write_all(&[*byte]).unwrap();
+    }
+    assert_eq!(writer.into_inner().unwrap(), b"a\xffb");
+}
+
+#[test]
+fn escapes_pass_through_by_default() {
+    let mut writer = Utf8Writer::new(Vec::new());
+    writer.write_all("a\u{10ffff}b".as_bytes()).unwrap();
+    assert_eq!(writer.into_inner().unwrap(), "a\u{10ffff}b".as_bytes());
+}
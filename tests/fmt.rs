@@ -0,0 +1,100 @@
+#![cfg(feature = "std")]
+
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use utf8_io::{copy_str, FmtAdaptor, FmtWriteStr, StrReader, Utf8Reader, Utf8Writer, WriteStr};
+
+/// A writer which fails every write.
+struct Failing;
+
+impl Write for Failing {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WriteStr for Failing {}
+
+#[test]
+fn fmt_adaptor() {
+    let mut adaptor = FmtAdaptor::new(Utf8Writer::new(Vec::new()));
+    write!(adaptor, "{} ☃ {:?}", 1, Some(2)).unwrap();
+    let vec = adaptor.into_inner().into_inner().unwrap();
+    assert_eq!(vec, "1 ☃ Some(2)".as_bytes());
+}
+
+#[test]
+fn fmt_adaptor_preserves_errors() {
+    let mut adaptor = FmtAdaptor::new(Failing);
+    let result = write!(adaptor, "hello");
+    assert!(result.is_err());
+    assert_eq!(
+        adaptor.io_result(result).unwrap_err().kind(),
+        io::ErrorKind::BrokenPipe
+    );
+    assert!(adaptor.take_error().is_none());
+
+    assert_eq!(
+        std::write!(Failing, "hello").unwrap_err().kind(),
+        io::ErrorKind::BrokenPipe
+    );
+}
+
+#[test]
+fn fmt_write_str_into_string() {
+    let mut s = String::from("prefix:");
+    copy_str(
+        &mut Utf8Reader::new("w\u{f6}rld ☃".as_bytes()),
+        &mut FmtWriteStr::new(&mut s),
+    )
+    .unwrap();
+    assert_eq!(s, "prefix:w\u{f6}rld ☃");
+}
+
+#[test]
+fn fmt_write_str_bytes() {
+    let mut writer = FmtWriteStr::new(String::new());
+    let bytes = "a☃💩".as_bytes();
+
+    // Write a byte at a time, splitting scalar values' encodings.
+    for byte in bytes {
+        writer.write_all(&[*byte]).unwrap();
+    }
+    write!(writer, " {}", 42).unwrap();
+    assert_eq!(writer.into_inner().unwrap(), "a☃💩 42");
+
+    let mut writer = FmtWriteStr::new(String::new());
+    assert_eq!(
+        writer.write_all(b"a\xffb").unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(writer.get_ref(), "a");
+
+    let mut writer = FmtWriteStr::new(String::new());
+    writer.write_all(b"\xE2\x98").unwrap();
+    assert_eq!(
+        writer.into_inner().unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+/// A `Display` implementation which streams its text from a reader.
+struct Streamed(&'static str);
+
+impl fmt::Display for Streamed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        copy_str(&mut StrReader::new(self.0), &mut FmtWriteStr::new(f))
+            .map(|_| ())
+            .map_err(|_| fmt::Error)
+    }
+}
+
+#[test]
+fn fmt_write_str_into_formatter() {
+    assert_eq!(Streamed("hello ☃").to_string(), "hello ☃");
+    assert_eq!(format!("[{}]", Streamed("x")), "[x]");
+}
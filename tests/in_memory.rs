@@ -0,0 +1,169 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, BufRead, Cursor, Read};
+use utf8_io::{copy_str, BufReadStr, ChunksReader, ReadStr, StrReader, Utf8Writer};
+
+const TEXT: &str = "hello w\u{f6}rld ☃💩 and more";
+
+/// Read all of `reader` with `read_str` calls of `buf_len` bytes.
+fn read_all<R: ReadStr>(mut reader: R, buf_len: usize) -> String {
+    let mut s = String::new();
+    let mut buf = "\0".repeat(buf_len);
+    loop {
+        match reader.read_str(&mut buf) {
+            Ok(0) => return s,
+            Ok(size) => s.push_str(&buf[..size]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+fn chunks() -> ChunksReader<std::vec::IntoIter<String>> {
+    ChunksReader::new(vec![
+        "hello ".to_owned(),
+        String::new(),
+        "w\u{f6}rld ☃".to_owned(),
+        "💩".to_owned(),
+        " and more".to_owned(),
+    ])
+}
+
+#[test]
+fn read_str() {
+    for buf_len in [4, 5, 7, 64] {
+        assert_eq!(read_all(StrReader::new(TEXT), buf_len), TEXT);
+        assert_eq!(read_all(Cursor::new(TEXT), buf_len), TEXT);
+        assert_eq!(read_all(Cursor::new(TEXT.to_owned()), buf_len), TEXT);
+        assert_eq!(read_all(chunks(), buf_len), TEXT);
+        assert_eq!(
+            read_all(ChunksReader::new(TEXT.split_inclusive(' ')), buf_len),
+            TEXT
+        );
+    }
+}
+
+#[test]
+fn short_buffer() {
+    let mut reader = StrReader::new("a☃");
+    let mut buf = "\0".repeat(2);
+    assert_eq!(reader.read_str(&mut buf).unwrap(), 1);
+    assert_eq!(
+        reader.read_str(&mut buf).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(reader.remaining(), "☃");
+
+    let mut buf = [0; 2];
+    assert_eq!(
+        chunks().read(&mut buf[..0]).unwrap(),
+        0,
+        "an empty buffer reads nothing"
+    );
+}
+
+#[test]
+fn read_bytes() {
+    // Byte-level reads split scalar values when nothing else fits.
+    let bytes = StrReader::new(TEXT)
+        .bytes()
+        .collect::<io::Result<Vec<u8>>>();
+    assert_eq!(bytes.unwrap(), TEXT.as_bytes());
+    let bytes = chunks().bytes().collect::<io::Result<Vec<u8>>>();
+    assert_eq!(bytes.unwrap(), TEXT.as_bytes());
+
+    let mut reader = StrReader::new("a☃");
+    let mut buf = [0; 2];
+    assert_eq!(reader.read(&mut buf).unwrap(), 1);
+    assert_eq!(reader.read(&mut buf).unwrap(), 2);
+    assert_eq!(buf, [0xe2, 0x98]);
+    assert_eq!(
+        reader.read_char().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(reader.read(&mut buf).unwrap(), 1);
+    assert_eq!(buf[0], 0x83);
+    assert_eq!(reader.read_char().unwrap(), None);
+
+    for buf_len in 1..4 {
+        let mut v = Vec::new();
+        let mut buf = vec![0; buf_len];
+        let mut reader = chunks();
+        loop {
+            match reader.read(&mut buf).unwrap() {
+                0 => break,
+                size => v.extend_from_slice(&buf[..size]),
+            }
+        }
+        assert_eq!(v, TEXT.as_bytes());
+    }
+}
+
+#[test]
+fn chars() {
+    let mut reader = StrReader::new(TEXT);
+    assert_eq!(reader.peek_char().unwrap(), Some('h'));
+    let s = reader.chars().collect::<io::Result<String>>().unwrap();
+    assert_eq!(s, TEXT);
+    assert_eq!(reader.position(), TEXT.len());
+
+    let s = chunks().chars().collect::<io::Result<String>>().unwrap();
+    assert_eq!(s, TEXT);
+
+    let mut cursor = Cursor::new(TEXT);
+    assert_eq!(cursor.peek_char().unwrap(), Some('h'));
+    let s = cursor.chars().collect::<io::Result<String>>().unwrap();
+    assert_eq!(s, TEXT);
+    assert_eq!(cursor.peek_char().unwrap(), None);
+}
+
+#[test]
+fn to_end() {
+    let mut s = String::from("prefix:");
+    StrReader::new(TEXT).read_str_to_end(&mut s).unwrap();
+    assert_eq!(s, format!("prefix:{}", TEXT));
+
+    let mut s = String::new();
+    chunks().read_to_string(&mut s).unwrap();
+    assert_eq!(s, TEXT);
+
+    let mut s = String::new();
+    Cursor::new(TEXT).read_str_to_end(&mut s).unwrap();
+    assert_eq!(s, TEXT);
+}
+
+#[test]
+fn lines() {
+    let lines = ChunksReader::new(["one\ntw", "o\n", "three"])
+        .lines_str()
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(lines, ["one", "two", "three"]);
+
+    let mut reader = StrReader::new("a\nb");
+    assert_eq!(reader.fill_buf().unwrap(), b"a\nb");
+    reader.consume(2);
+    assert_eq!(reader.fill_buf_str().unwrap(), "b");
+}
+
+#[test]
+fn copy() {
+    let mut output = Utf8Writer::new(Vec::new());
+    copy_str(&mut chunks(), &mut output).unwrap();
+    assert_eq!(output.into_inner().unwrap(), TEXT.as_bytes());
+
+    let mut v = Vec::new();
+    copy_str(&mut StrReader::new(TEXT), &mut v).unwrap();
+    assert_eq!(v, TEXT.as_bytes());
+}
+
+#[test]
+fn cursor_mid_char() {
+    let mut cursor = Cursor::new("☃");
+    cursor.set_position(1);
+    let mut buf = "\0".repeat(4);
+    assert_eq!(
+        cursor.read_str(&mut buf).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
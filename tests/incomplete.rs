@@ -1,5 +1,7 @@
+#![cfg(feature = "std")]
+
 use std::io::{self, Write};
-use utf8_io::Utf8Writer;
+use utf8_io::{FmtWriteStr, Utf8Error, Utf8Writer, WriteStr};
 
 #[test]
 fn incomplete_c() {
@@ -113,3 +115,92 @@ fn complete_f() {
     writer.flush().unwrap();
     assert_eq!(&writer.into_inner().unwrap(), b"hello\xf1\x80\x80\x80world");
 }
+
+#[test]
+fn invalid_completion() {
+    let mut writer = Utf8Writer::new(Vec::new());
+    writer.write_all(b"hello\xe1").unwrap();
+    assert_eq!(
+        writer.write_all(b"AA").unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(&writer.into_inner().unwrap(), b"hello");
+}
+
+/// Return the `Utf8Error` inside `err`.
+fn utf8_error(err: &io::Error) -> &Utf8Error {
+    err.get_ref().unwrap().downcast_ref::<Utf8Error>().unwrap()
+}
+
+#[test]
+fn incomplete_error_details() {
+    let mut writer = Utf8Writer::new(Vec::new());
+    writer.write_all(b"hello\xf1\x80").unwrap();
+    let err = writer.flush().unwrap_err();
+    assert_eq!(
+        utf8_error(&err),
+        &Utf8Error::IncompleteAtFlush {
+            offset: 5,
+            bytes: b"\xf1\x80".to_vec()
+        }
+    );
+    writer.flush().unwrap();
+}
+
+#[test]
+fn invalid_error_details() {
+    let mut writer = Utf8Writer::new(Vec::new());
+    let err = writer.write_all(b"hello\xffworld").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    match utf8_error(&err) {
+        Utf8Error::InvalidSequence(invalid) => {
+            assert_eq!(invalid.offset(), 5);
+            assert_eq!(invalid.bytes(), b"\xff");
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+
+    let mut writer = Utf8Writer::new(Vec::new());
+    writer.write_all(b"ab\xe1").unwrap();
+    let err = writer.write_all(b"AA").unwrap_err();
+    match utf8_error(&err) {
+        Utf8Error::InvalidSequence(invalid) => {
+            assert_eq!(invalid.offset(), 2);
+            assert_eq!(invalid.bytes(), b"\xe1");
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+#[should_panic(expected = "at byte 5: e1")]
+fn incomplete_at_drop() {
+    let mut writer = Utf8Writer::new(Vec::new());
+    writer.write_all(b"hello\xe1").unwrap();
+}
+
+#[test]
+fn write_str_while_incomplete() {
+    let mut writer = Utf8Writer::new(Vec::new());
+    writer.write_all(b"\xe2").unwrap();
+    let err = writer.write_str("a").unwrap_err();
+    assert_eq!(
+        utf8_error(&err),
+        &Utf8Error::IncompleteAtWriteStr {
+            offset: 0,
+            bytes: b"\xe2".to_vec()
+        }
+    );
+    writer.write_all(b"\x98\x83").unwrap();
+    assert_eq!(&writer.into_inner().unwrap(), "☃".as_bytes());
+
+    let mut writer = FmtWriteStr::new(String::new());
+    writer.write_all(b"\xe2").unwrap();
+    let err = writer.write_str("a").unwrap_err();
+    assert!(matches!(
+        utf8_error(&err),
+        Utf8Error::IncompleteAtWriteStr { offset: 0, .. }
+    ));
+    writer.write_all(b"\x98\x83").unwrap();
+    assert_eq!(writer.into_inner().unwrap(), "☃");
+}
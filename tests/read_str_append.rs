@@ -0,0 +1,155 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, Read};
+use utf8_io::{InvalidPolicy, ReadStr, Utf8BufReader, Utf8Reader};
+
+const TEXT: &[u8] = b"hello w\xC3\xB6rld \xE2\x98\x83\xF0\x9F\x92\xA9 and more";
+
+/// A reader which produces one byte at a time.
+struct OneByteReader<'a>(&'a [u8]);
+
+impl Read for OneByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.take(buf.len().min(1) as u64).read(buf).inspect(|n| {
+            self.0 = &self.0[*n..];
+        })
+    }
+}
+
+/// A reader which fails after producing its input.
+struct FailingReader<'a>(&'a [u8]);
+
+impl Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Err(io::Error::other("oops"));
+        }
+        let n = self.0.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+/// A `ReadStr` which only implements `read_str`, to exercise the default
+/// implementations.
+struct Basic<Inner>(Inner);
+
+impl<Inner: ReadStr> Read for Basic<Inner> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<Inner: ReadStr> ReadStr for Basic<Inner> {
+    fn read_str(&mut self, buf: &mut str) -> io::Result<usize> {
+        self.0.read_str(buf)
+    }
+}
+
+fn append_all<R: ReadStr>(mut reader: R, max: usize) -> String {
+    let mut s = String::from("prefix:");
+    loop {
+        match reader.read_str_append(&mut s, max) {
+            Ok(0) => return s,
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+#[test]
+fn read_str_append() {
+    let expected = "prefix:hello wörld ☃💩 and more";
+    for max in [4, 5, 7, 64] {
+        assert_eq!(append_all(Utf8Reader::new(TEXT), max), expected);
+        assert_eq!(
+            append_all(Utf8Reader::new(OneByteReader(TEXT)), max),
+            expected
+        );
+        assert_eq!(
+            append_all(Utf8BufReader::with_capacity(8, Utf8Reader::new(TEXT)), max),
+            expected
+        );
+        assert_eq!(append_all(Basic(Utf8Reader::new(TEXT)), max), expected);
+    }
+}
+
+#[test]
+fn read_str_append_respects_max() {
+    let mut reader = Utf8Reader::new(TEXT);
+    let mut s = String::new();
+    assert_eq!(reader.read_str_append(&mut s, 9).unwrap(), 9);
+    assert_eq!(s, "hello w\u{f6}");
+    assert_eq!(reader.read_str_append(&mut s, 4).unwrap(), 4);
+    assert_eq!(s, "hello w\u{f6}rld ");
+    assert_eq!(reader.read_str_append(&mut s, 4).unwrap(), 3);
+    assert_eq!(s, "hello w\u{f6}rld ☃");
+}
+
+#[test]
+fn read_str_to_end() {
+    let expected = "prefix:hello wörld ☃💩 and more";
+
+    let mut s = String::from("prefix:");
+    let n = Utf8Reader::new(OneByteReader(TEXT))
+        .read_str_to_end(&mut s)
+        .unwrap();
+    assert_eq!(s, expected);
+    assert_eq!(n, TEXT.len());
+
+    let mut s = String::from("prefix:");
+    Basic(Utf8Reader::new(OneByteReader(TEXT)))
+        .read_str_to_end(&mut s)
+        .unwrap();
+    assert_eq!(s, expected);
+
+    let mut s = String::from("prefix:");
+    Utf8BufReader::with_capacity(4, Utf8Reader::new(TEXT))
+        .read_str_to_end(&mut s)
+        .unwrap();
+    assert_eq!(s, expected);
+}
+
+#[test]
+fn read_str_to_end_large() {
+    let text = "☃💩 snowman ".repeat(10_000);
+    let mut s = String::new();
+    Utf8Reader::new(text.as_bytes())
+        .read_str_to_end(&mut s)
+        .unwrap();
+    assert_eq!(s, text);
+}
+
+#[test]
+fn read_str_append_error_leaves_string_valid() {
+    // The reader fails while holding an incomplete sequence.
+    let mut reader = Utf8Reader::new(FailingReader(b"ab\xE2\x98"));
+    let mut s = String::from("x");
+    assert_eq!(reader.read_str_append(&mut s, 64).unwrap(), 2);
+    assert_eq!(s, "xab");
+    reader.read_str_append(&mut s, 64).unwrap_err();
+    assert_eq!(s, "xab");
+
+    let mut s = String::from("x");
+    Utf8Reader::new(FailingReader(b"ab\xE2\x98"))
+        .read_str_to_end(&mut s)
+        .unwrap_err();
+    assert_eq!(s, "xab");
+}
+
+#[test]
+fn read_str_append_invalid() {
+    let mut s = String::new();
+    Utf8Reader::with_policy(&b"a\xffb"[..], InvalidPolicy::Replace)
+        .read_str_to_end(&mut s)
+        .unwrap();
+    assert_eq!(s, "a\u{fffd}b");
+
+    let mut s = String::from("x");
+    Utf8Reader::with_policy(&b"a\xffb"[..], InvalidPolicy::Error)
+        .read_str_to_end(&mut s)
+        .unwrap_err();
+    assert!(s.starts_with('x'));
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "std")]
+
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, LineWriter, Write};
+use std::net::TcpStream;
+use utf8_io::{copy_str, Utf8Reader, Utf8Writer, WriteStr};
+
+/// Write some text using a `WriteStr` bound.
+fn write_text<W: WriteStr>(mut writer: W) -> io::Result<()> {
+    writer.write_str("hello ")?;
+    writer.write_str("w\u{f6}rld ☃\n")?;
+    writer.flush()
+}
+
+const TEXT: &[u8] = "hello w\u{f6}rld ☃\n".as_bytes();
+
+#[test]
+fn vec() {
+    let mut v = Vec::new();
+    write_text(&mut v).unwrap();
+    assert_eq!(v, TEXT);
+}
+
+#[test]
+fn cursor() {
+    let mut cursor = Cursor::new(Vec::new());
+    write_text(&mut cursor).unwrap();
+    assert_eq!(cursor.into_inner(), TEXT);
+
+    let mut array = [0; 64];
+    let mut cursor = Cursor::new(&mut array[..]);
+    write_text(&mut cursor).unwrap();
+    let len = cursor.position() as usize;
+    assert_eq!(&array[..len], TEXT);
+}
+
+#[test]
+fn buffered() {
+    let mut buf_writer = BufWriter::new(Vec::new());
+    write_text(&mut buf_writer).unwrap();
+    assert_eq!(buf_writer.into_inner().unwrap(), TEXT);
+
+    let mut line_writer = LineWriter::new(Vec::new());
+    write_text(&mut line_writer).unwrap();
+    assert_eq!(line_writer.into_inner().unwrap(), TEXT);
+
+    // A `BufWriter` around a `Utf8Writer`.
+    let mut buf_writer = BufWriter::new(Utf8Writer::new(Vec::new()));
+    write_text(&mut buf_writer).unwrap();
+    let utf8_writer = buf_writer.into_inner().unwrap();
+    assert_eq!(utf8_writer.into_inner().unwrap(), TEXT);
+}
+
+#[test]
+fn boxed() {
+    let mut boxed: Box<dyn WriteStr> = Box::new(Vec::new());
+    write_text(&mut boxed).unwrap();
+    write_text(Box::new(io::sink())).unwrap();
+}
+
+#[test]
+fn std_streams() {
+    // Avoid writing to the real streams; just check that they're accepted.
+    fn assert_write_str<W: WriteStr>() {}
+    assert_write_str::<io::Stdout>();
+    assert_write_str::<io::StdoutLock<'static>>();
+    assert_write_str::<io::Stderr>();
+    assert_write_str::<io::StderrLock<'static>>();
+    assert_write_str::<File>();
+    assert_write_str::<&File>();
+    assert_write_str::<TcpStream>();
+    assert_write_str::<&mut Utf8Writer<File>>();
+}
+
+#[test]
+fn copy_to_vec() {
+    let mut v = Vec::new();
+    copy_str(&mut Utf8Reader::new(TEXT), &mut v).unwrap();
+    assert_eq!(v, TEXT);
+}
+
+/// A writer which accepts at most two bytes at a time, and returns
+/// `WouldBlock` on every other call, like a congested non-blocking socket.
+struct Congested {
+    data: Vec<u8>,
+    ready: bool,
+}
+
+impl Write for Congested {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.ready = !self.ready;
+        if !self.ready {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let n = buf.len().min(2);
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WriteStr for Congested {}
+
+#[test]
+fn write_str_partial_would_block() {
+    let text = "w\u{f6}rld ☃💩";
+    let mut writer = Utf8Writer::new(Congested {
+        data: Vec::new(),
+        ready: false,
+    });
+    let mut rest = text;
+    while !rest.is_empty() {
+        match writer.write_str_partial(rest) {
+            Ok(n) => {
+                assert!(n != 0);
+                rest = &rest[n..];
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => panic!("{}", err),
+        }
+    }
+    loop {
+        match writer.flush() {
+            Ok(()) => break,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => panic!("{}", err),
+        }
+    }
+    assert_eq!(writer.into_inner().unwrap().data, text.as_bytes());
+}
+
+#[test]
+fn write_str_partial_default() {
+    let mut v = Vec::new();
+    assert_eq!(v.write_str_partial("☃a").unwrap(), 4);
+    assert_eq!(v, "☃a".as_bytes());
+
+    // A stream which accepts part of an encoding and then blocks gets a
+    // count of the bytes it accepted, rather than an error.
+    let mut congested = Congested {
+        data: Vec::new(),
+        ready: false,
+    };
+    assert_eq!(congested.write_str_partial("☃").unwrap(), 2);
+    assert_eq!(congested.data, b"\xE2\x98");
+    assert_eq!(congested.write(&"☃".as_bytes()[2..]).unwrap(), 1);
+    assert_eq!(congested.data, "☃".as_bytes());
+}
+
+/// A writer which accepts at most `limit` bytes per call, gathering them
+/// from all the slices of a vectored write.
+struct Limited {
+    data: Vec<u8>,
+    limit: usize,
+    calls: usize,
+}
+
+impl Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[io::IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.calls += 1;
+        let mut n = 0;
+        for buf in bufs {
+            let len = buf.len().min(self.limit - n);
+            self.data.extend_from_slice(&buf[..len]);
+            n += len;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_vectored() {
+    let pieces: [&[u8]; 4] = [b"w\xc3", b"\xb6rld \xe2", b"\x98", b"\x83 \xf0\x9f\x92\xa9"];
+    let text = "w\u{f6}rld ☃ 💩";
+    for limit in [1, 2, 3, 5, 64] {
+        let mut writer = Utf8Writer::new(Limited {
+            data: Vec::new(),
+            limit,
+            calls: 0,
+        });
+        let mut slices: Vec<io::IoSlice<'_>> = pieces.iter().map(|p| io::IoSlice::new(p)).collect();
+        let mut slices = &mut slices[..];
+        while !slices.is_empty() {
+            let n = writer.write_vectored(slices).unwrap();
+            io::IoSlice::advance_slices(&mut slices, n);
+        }
+        let inner = writer.into_inner().unwrap();
+        assert_eq!(inner.data, text.as_bytes(), "limit {}", limit);
+        if limit == 64 {
+            assert_eq!(inner.calls, 1);
+        }
+    }
+
+    // Invalid input is reported once the valid prefix has been written.
+    let mut writer = Utf8Writer::new(Vec::new());
+    let slices = [io::IoSlice::new(b"ab\xe2"), io::IoSlice::new(b"\x98c")];
+    assert_eq!(writer.write_vectored(&slices).unwrap(), 2);
+    assert_eq!(
+        writer
+            .write_vectored(&[io::IoSlice::new(b"\xe2"), io::IoSlice::new(b"\x98c")])
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        writer.write(b"\x98c").unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn write_str_vectored() {
+    let mut v = Vec::new();
+    v.write_str_vectored(&["", "hello ", "w\u{f6}rld", "", " ☃\n"])
+        .unwrap();
+    assert_eq!(v, TEXT);
+
+    let mut writer = Utf8Writer::new(Limited {
+        data: Vec::new(),
+        limit: 3,
+        calls: 0,
+    });
+    writer
+        .write_str_vectored(&["hello ", "w\u{f6}rld", " ☃\n"])
+        .unwrap();
+    assert_eq!(writer.into_inner().unwrap().data, TEXT);
+}